@@ -0,0 +1,281 @@
+use crate::error::{Result, RexerError};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{debug, info};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use url::Url;
+
+pub struct ArchiveManager;
+
+impl ArchiveManager {
+    /// Download the archive, verify its checksum and extract it to the destination
+    pub async fn install(
+        url: &str,
+        sha256: &str,
+        strip_components: usize,
+        destination: &Path,
+    ) -> Result<()> {
+        let bytes = Self::download(url).await?;
+
+        // Verify before anything is written next to the destination
+        Self::verify_checksum(url, &bytes, sha256)?;
+
+        let parent = destination.parent().ok_or_else(|| {
+            RexerError::ArchiveError(format!("Invalid destination: {}", destination.display()))
+        })?;
+        fs::create_dir_all(parent)?;
+
+        // Extract into a temporary directory first so a broken archive leaves the destination untouched
+        let staging_dir = tempfile::tempdir_in(parent)?;
+        Self::extract(&bytes, strip_components, staging_dir.path())?;
+
+        if destination.exists() {
+            fs::remove_dir_all(destination)?;
+        }
+        fs::rename(staging_dir.keep(), destination)?;
+
+        Ok(())
+    }
+
+    /// Extract a local archive into the destination directory
+    pub fn extract_file(archive: &Path, destination: &Path) -> Result<()> {
+        let bytes = fs::read(archive).map_err(|e| {
+            RexerError::ArchiveError(format!("Failed to read {}: {e}", archive.display()))
+        })?;
+        fs::create_dir_all(destination)?;
+        Self::extract(&bytes, 0, destination)
+    }
+
+    /// Write a gzipped tarball of the given files and directories, each stored under
+    /// its name in the archive. `.git` directories are left out and symlinks are kept.
+    pub fn create(output: &Path, entries: &[(PathBuf, PathBuf)]) -> Result<()> {
+        let file = fs::File::create(output)?;
+        let mut builder = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        builder.follow_symlinks(false);
+
+        for (path, name) in entries {
+            Self::append(&mut builder, path, name)?;
+        }
+
+        builder.into_inner()?.finish()?;
+        Ok(())
+    }
+
+    fn append<W: Write>(builder: &mut tar::Builder<W>, path: &Path, name: &Path) -> Result<()> {
+        let file_type = fs::symlink_metadata(path)?.file_type();
+        if !file_type.is_dir() {
+            builder.append_path_with_name(path, name)?;
+            return Ok(());
+        }
+
+        builder.append_dir(name, path)?;
+        let mut children: Vec<_> = fs::read_dir(path)?.collect::<io::Result<_>>()?;
+        children.sort_by_key(|entry| entry.file_name());
+        for entry in children {
+            if entry.file_name() == ".git" {
+                continue;
+            }
+            Self::append(builder, &entry.path(), &name.join(entry.file_name()))?;
+        }
+
+        Ok(())
+    }
+
+    async fn download(url: &str) -> Result<Vec<u8>> {
+        info!("Downloading {url}");
+
+        let parsed = Url::parse(url)
+            .map_err(|e| RexerError::ArchiveError(format!("Invalid archive URL {url}: {e}")))?;
+
+        if parsed.scheme() == "file" {
+            let path = parsed
+                .to_file_path()
+                .map_err(|_| RexerError::ArchiveError(format!("Invalid file URL: {url}")))?;
+            return Ok(fs::read(path)?);
+        }
+
+        let response = reqwest::get(parsed)
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| RexerError::ArchiveError(format!("Failed to download {url}: {e}")))?;
+
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| RexerError::ArchiveError(format!("Failed to download {url}: {e}")))?;
+
+        Ok(bytes.to_vec())
+    }
+
+    fn verify_checksum(url: &str, bytes: &[u8], expected: &str) -> Result<()> {
+        let digest = format!("{:x}", Sha256::digest(bytes));
+
+        if !digest.eq_ignore_ascii_case(expected.trim()) {
+            return Err(RexerError::ArchiveError(format!(
+                "Checksum mismatch for {url}: expected {expected}, got {digest}"
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn extract(bytes: &[u8], strip_components: usize, destination: &Path) -> Result<()> {
+        // Detect the format from the magic bytes since download URLs rarely carry an extension
+        if bytes.starts_with(b"PK\x03\x04") {
+            Self::extract_zip(bytes, strip_components, destination)
+        } else if bytes.starts_with(&[0x1f, 0x8b]) {
+            Self::extract_tar(GzDecoder::new(bytes), strip_components, destination)
+        } else {
+            Self::extract_tar(bytes, strip_components, destination)
+        }
+    }
+
+    fn extract_tar<R: Read>(reader: R, strip_components: usize, destination: &Path) -> Result<()> {
+        let mut archive = tar::Archive::new(reader);
+
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = entry.path()?.into_owned();
+
+            let Some(relative_path) = Self::strip_path(&path, strip_components)? else {
+                continue;
+            };
+
+            let target = destination.join(&relative_path);
+            debug!("Extracting {}", target.display());
+            Self::ensure_no_symlink(destination, &relative_path)?;
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+
+            let entry_type = entry.header().entry_type();
+            if entry_type.is_symlink() || entry_type.is_hard_link() {
+                let link = entry
+                    .link_name()?
+                    .map(|link| link.into_owned())
+                    .ok_or_else(|| {
+                        RexerError::ArchiveError(format!(
+                            "Missing link target in archive: {}",
+                            path.display()
+                        ))
+                    })?;
+                if link.is_absolute() || link.components().any(|c| c == Component::ParentDir) {
+                    return Err(RexerError::ArchiveError(format!(
+                        "Unsafe link in archive: {} -> {}",
+                        path.display(),
+                        link.display()
+                    )));
+                }
+
+                if entry_type.is_hard_link() {
+                    // Hard link targets name another archive member, so they are
+                    // resolved against the destination rather than the working directory.
+                    let Some(source) = Self::strip_path(&link, strip_components)? else {
+                        return Err(RexerError::ArchiveError(format!(
+                            "Unsafe link in archive: {} -> {}",
+                            path.display(),
+                            link.display()
+                        )));
+                    };
+                    Self::ensure_no_symlink(destination, &source)?;
+                    fs::hard_link(destination.join(source), &target)?;
+                    continue;
+                }
+            }
+
+            entry.unpack(&target)?;
+        }
+
+        Ok(())
+    }
+
+    /// Refuse paths that would pass through a symlink extracted earlier,
+    /// which could otherwise redirect later entries outside the destination.
+    fn ensure_no_symlink(destination: &Path, relative_path: &Path) -> Result<()> {
+        let mut current = destination.to_path_buf();
+        let mut components = relative_path.components().peekable();
+        while let Some(component) = components.next() {
+            if components.peek().is_none() {
+                break;
+            }
+            current.push(component);
+            if fs::symlink_metadata(&current)
+                .is_ok_and(|metadata| metadata.file_type().is_symlink())
+            {
+                return Err(RexerError::ArchiveError(format!(
+                    "Unsafe path in archive: {} goes through a symlink",
+                    relative_path.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn extract_zip(bytes: &[u8], strip_components: usize, destination: &Path) -> Result<()> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+            .map_err(|e| RexerError::ArchiveError(format!("Invalid zip archive: {e}")))?;
+
+        for i in 0..archive.len() {
+            let mut file = archive
+                .by_index(i)
+                .map_err(|e| RexerError::ArchiveError(format!("Invalid zip archive: {e}")))?;
+
+            let path = file.enclosed_name().ok_or_else(|| {
+                RexerError::ArchiveError(format!("Unsafe path in archive: {}", file.name()))
+            })?;
+
+            let Some(relative_path) = Self::strip_path(&path, strip_components)? else {
+                continue;
+            };
+
+            let target = destination.join(&relative_path);
+            debug!("Extracting {}", target.display());
+            if file.is_dir() {
+                fs::create_dir_all(&target)?;
+                continue;
+            }
+
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut output = fs::File::create(&target)?;
+            io::copy(&mut file, &mut output)?;
+
+            #[cfg(unix)]
+            if let Some(mode) = file.unix_mode() {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&target, fs::Permissions::from_mode(mode))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drop the leading path components and reject paths escaping the destination.
+    /// Returns None for entries consumed entirely by the stripped components.
+    fn strip_path(path: &Path, strip_components: usize) -> Result<Option<PathBuf>> {
+        let mut components = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => components.push(name),
+                Component::CurDir => {}
+                _ => {
+                    return Err(RexerError::ArchiveError(format!(
+                        "Unsafe path in archive: {}",
+                        path.display()
+                    )))
+                }
+            }
+        }
+
+        if components.len() <= strip_components {
+            return Ok(None);
+        }
+
+        Ok(Some(components[strip_components..].iter().collect()))
+    }
+}
@@ -0,0 +1,175 @@
+use crate::error::Result;
+use crate::git::GitManager;
+use chrono::{DateTime, Utc};
+use log::debug;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Written into each mirror so unused mirrors can be pruned
+const LAST_USED_FILE: &str = "rexer-last-used";
+
+/// Whether each mirror was already refreshed by this process, so a repository shared
+/// by several extensions is fetched once per run. Extensions fetched at the same time
+/// wait for the refresh instead of reading the mirror before it.
+static REFRESHED: LazyLock<Mutex<HashMap<PathBuf, Arc<Mutex<bool>>>>> =
+    LazyLock::new(Default::default);
+
+/// Bare mirrors of remote repositories, shared by every Redmine root of the user.
+/// Extensions are cloned from a mirror, so only new objects are downloaded.
+pub struct MirrorCache {
+    root: PathBuf,
+}
+
+/// A mirror in the cache
+pub struct Mirror {
+    pub path: PathBuf,
+    pub url: Option<String>,
+    pub last_used: Option<DateTime<Utc>>,
+    pub size: u64,
+}
+
+impl MirrorCache {
+    /// The cache under `REXER_CACHE_DIR`, or `rexer` in the user's cache directory
+    pub fn open() -> Option<Self> {
+        let root = match std::env::var_os("REXER_CACHE_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => dirs::cache_dir()?.join("rexer"),
+        };
+
+        Some(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn mirrors_dir(&self) -> PathBuf {
+        self.root.join("mirrors")
+    }
+
+    pub fn mirror_path(&self, url: &str) -> PathBuf {
+        self.mirrors_dir()
+            .join(GitManager::repository_dir_name(url))
+    }
+
+    /// Create or fetch the mirror of the repository and return its path
+    pub fn refresh(&self, url: &str) -> Result<PathBuf> {
+        let path = self.mirror_path(url);
+
+        let state = match REFRESHED.lock() {
+            Ok(mut mirrors) => Arc::clone(mirrors.entry(path.clone()).or_default()),
+            Err(_) => Arc::default(),
+        };
+        let mut refreshed = state.lock().unwrap_or_else(|e| e.into_inner());
+        if !*refreshed {
+            if path.exists() {
+                debug!("Fetching mirror {}", path.display());
+                GitManager::fetch_mirror(&path)?;
+            } else {
+                self.create(url, &path)?;
+            }
+            *refreshed = true;
+        }
+
+        fs::write(path.join(LAST_USED_FILE), Utc::now().to_rfc3339())?;
+        Ok(path)
+    }
+
+    /// Clone next to the final path first, so another rex never sees a partial mirror
+    fn create(&self, url: &str, path: &Path) -> Result<()> {
+        let mirrors_dir = self.mirrors_dir();
+        fs::create_dir_all(&mirrors_dir)?;
+
+        let work_dir = tempfile::Builder::new()
+            .prefix(".mirror")
+            .tempdir_in(&mirrors_dir)?;
+        let cloned = work_dir.path().join("repo.git");
+        debug!("Creating mirror of {url} at {}", path.display());
+        GitManager::clone_mirror(url, &cloned)?;
+
+        // Another process may have created the same mirror in the meantime
+        if let Err(e) = fs::rename(&cloned, path) {
+            if !path.exists() {
+                return Err(e.into());
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn mirrors(&self) -> Result<Vec<Mirror>> {
+        let mirrors_dir = self.mirrors_dir();
+        if !mirrors_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut mirrors = Vec::new();
+        for entry in fs::read_dir(&mirrors_dir)? {
+            let path = entry?.path();
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if hidden || !path.is_dir() {
+                continue;
+            }
+
+            let last_used = fs::read_to_string(path.join(LAST_USED_FILE))
+                .ok()
+                .and_then(|time| DateTime::parse_from_rfc3339(time.trim()).ok())
+                .map(|time| time.with_timezone(&Utc));
+            mirrors.push(Mirror {
+                url: GitManager::remote_url(&path).ok().flatten(),
+                last_used,
+                size: dir_size(&path)?,
+                path,
+            });
+        }
+        mirrors.sort_by(|a, b| a.url.cmp(&b.url));
+
+        Ok(mirrors)
+    }
+}
+
+/// Where a repository can be read from without the network: its local path, or its
+/// mirror in the cache
+pub fn offline_location(url: &str) -> Option<String> {
+    if !is_remote(url) {
+        return Some(url.to_string());
+    }
+
+    let mirror = MirrorCache::open()?.mirror_path(url);
+    mirror
+        .is_dir()
+        .then(|| mirror.to_string_lossy().to_string())
+}
+
+/// Remote repositories are mirrored. Plain local paths are cloned directly, which
+/// is already as fast as cloning a mirror.
+pub fn is_remote(url: &str) -> bool {
+    if url.contains("://") {
+        return true;
+    }
+
+    // scp-like syntax such as git@github.com:owner/repo.git
+    match url.split_once(':') {
+        Some((host, _)) => !host.is_empty() && !host.contains('/') && host.len() > 1,
+        None => false,
+    }
+}
+
+fn dir_size(path: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+
+    Ok(size)
+}
@@ -1,6 +1,12 @@
-use crate::commands::{edit, init, install, reinstall, state, uninstall, update};
-use crate::error::Result;
-use clap::{Parser, Subcommand};
+use crate::commands::{
+    add, cache_clean, cache_list, cache_prune, check, doctor, edit, envs, import, init, install,
+    outdated, package, reinstall, remove, state, status, switch, uninstall, update, InstallOptions,
+};
+use crate::config::DEFAULT_JOBS;
+use crate::error::{Result, RexerError};
+use crate::extension::{Extension, ExtensionType, GitOptions, Source, Submodules};
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "rex")]
@@ -25,33 +31,315 @@ pub enum Commands {
     Init,
 
     /// Install extensions defined in .extensions.yml
-    Install,
+    Install(InstallArgs),
 
     /// Uninstall all currently installed extensions
-    Uninstall,
+    Uninstall {
+        /// Keep the database tables of uninstalled plugins
+        #[arg(long)]
+        keep_data: bool,
+    },
 
     /// Reinstall specific extension
     Reinstall {
         /// Extension name to reinstall
         extension: String,
+
+        /// Overwrite local changes in the extension directory
+        #[arg(long)]
+        force: bool,
     },
 
     /// Update extensions to latest versions from lock file sources
     Update {
         /// Specific extensions to update (default: all)
         extensions: Vec<String>,
+
+        /// Number of extensions to fetch concurrently
+        #[arg(short, long, default_value_t = DEFAULT_JOBS)]
+        jobs: usize,
+
+        /// Overwrite local changes in extension directories
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Add an extension to .extensions.yml
+    Add(Box<AddArgs>),
+
+    /// Remove an extension from .extensions.yml
+    Remove {
+        /// Extension name to remove
+        name: String,
+
+        /// Uninstall the extension right away, leaving other changes to .extensions.yml alone
+        #[arg(long)]
+        install: bool,
+
+        /// Keep the database tables of the uninstalled plugin
+        #[arg(long, requires = "install")]
+        keep_data: bool,
     },
 
+    /// Add plugins and themes cloned by hand to .extensions.yml and the lock file
+    Import,
+
     /// Show current state of installed extensions
     State,
 
+    /// Validate .extensions.yml without installing anything
+    Check,
+
+    /// Check installed extensions against the lock file for local changes
+    #[command(alias = "verify")]
+    Status,
+
+    /// Check installed plugins against the Redmine version and their required plugins
+    Doctor,
+
+    /// Show extensions with newer commits or tags upstream
+    Outdated,
+
+    /// Switch to another environment defined in .extensions.yml
+    Switch {
+        /// Environment name to switch to
+        env: String,
+
+        /// Keep the database tables of removed plugins
+        #[arg(long)]
+        keep_data: bool,
+    },
+
+    /// List environments defined in .extensions.yml
+    Envs,
+
     /// Edit .extensions.yml file
     Edit,
 
+    /// Export the extensions of .extensions.lock into a tarball for rex install --from-package
+    Package {
+        /// Path of the tarball to write, such as extensions.tar.gz
+        output: PathBuf,
+
+        /// Number of extensions to fetch concurrently
+        #[arg(short, long, default_value_t = DEFAULT_JOBS)]
+        jobs: usize,
+    },
+
+    /// Manage the repository mirrors shared by all Redmine roots
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+
     /// Show version information
     Version,
 }
 
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// List the cached mirrors with their size and last use
+    List,
+
+    /// Remove all cached mirrors
+    Clean,
+
+    /// Remove mirrors not used recently
+    Prune {
+        /// Remove mirrors not used for this many days
+        #[arg(long, default_value_t = 30)]
+        days: u64,
+    },
+}
+
+#[derive(Args)]
+pub struct InstallArgs {
+    /// Keep the database tables of removed plugins
+    #[arg(long)]
+    pub keep_data: bool,
+
+    /// Show what would be installed, updated or removed without changing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Print the dry run plan as JSON
+    #[arg(long, requires = "dry_run")]
+    pub json: bool,
+
+    /// Number of extensions to fetch concurrently
+    #[arg(short, long, default_value_t = DEFAULT_JOBS)]
+    pub jobs: usize,
+
+    /// Install the commits recorded in .extensions.lock, failing if .extensions.yml changed
+    #[arg(long, conflicts_with = "dry_run")]
+    pub frozen: bool,
+
+    /// Install plugins even if they declare that they don't support this Redmine version
+    #[arg(long)]
+    pub ignore_compat: bool,
+
+    /// Install the commits in .extensions.lock from the mirror cache, without network access
+    #[arg(long, conflicts_with = "dry_run")]
+    pub offline: bool,
+
+    /// Install the extensions of a tarball written by rex package instead of fetching them
+    #[arg(long, value_name = "FILE", conflicts_with = "dry_run")]
+    pub from_package: Option<PathBuf>,
+}
+
+impl From<InstallArgs> for InstallOptions {
+    fn from(args: InstallArgs) -> Self {
+        Self {
+            keep_data: args.keep_data,
+            dry_run: args.dry_run,
+            json: args.json,
+            jobs: args.jobs,
+            frozen: args.frozen,
+            ignore_compat: args.ignore_compat,
+            offline: args.offline,
+            from_package: args.from_package,
+        }
+    }
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("reference").multiple(false)))]
+pub struct AddArgs {
+    /// Extension name, also its directory name under plugins/ or themes/
+    pub name: String,
+
+    /// Add a theme instead of a plugin
+    #[arg(long)]
+    pub theme: bool,
+
+    #[command(flatten)]
+    pub source: SourceArgs,
+
+    /// Host of a self-hosted GitLab, Bitbucket or Gitea server
+    #[arg(long)]
+    pub host: Option<String>,
+
+    /// Branch to track
+    #[arg(long, group = "reference")]
+    pub branch: Option<String>,
+
+    /// Tag to install
+    #[arg(long, group = "reference")]
+    pub tag: Option<String>,
+
+    /// Commit to install
+    #[arg(long, group = "reference")]
+    pub commit: Option<String>,
+
+    /// Version constraint resolved against the tags, such as "~> 1.2"
+    #[arg(long, group = "reference")]
+    pub version: Option<String>,
+
+    /// Subdirectory of the repository containing the extension
+    #[arg(long)]
+    pub subdir: Option<String>,
+
+    /// Install the extension right away, leaving other changes to .extensions.yml alone
+    #[arg(long)]
+    pub install: bool,
+
+    /// Keep the database tables of plugins removed by --install
+    #[arg(long, requires = "install")]
+    pub keep_data: bool,
+}
+
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+pub struct SourceArgs {
+    /// Git repository URL
+    #[arg(long)]
+    pub git: Option<String>,
+
+    /// GitHub repository as owner/repo
+    #[arg(long)]
+    pub github: Option<String>,
+
+    /// GitLab repository as owner/repo
+    #[arg(long)]
+    pub gitlab: Option<String>,
+
+    /// Bitbucket repository as owner/repo
+    #[arg(long)]
+    pub bitbucket: Option<String>,
+
+    /// Gitea repository as owner/repo
+    #[arg(long)]
+    pub gitea: Option<String>,
+}
+
+impl AddArgs {
+    fn into_extension(self) -> Result<(Extension, ExtensionType)> {
+        let options = GitOptions {
+            branch: self.branch,
+            tag: self.tag,
+            commit: self.commit,
+            version: self.version,
+            path: self.subdir,
+            clone_mode: None,
+            submodules: Submodules::Off,
+        };
+
+        let SourceArgs {
+            git,
+            github,
+            gitlab,
+            bitbucket,
+            gitea,
+        } = self.source;
+        let host = self.host;
+
+        if host.is_some() && (git.is_some() || github.is_some()) {
+            return Err(RexerError::InvalidConfig(
+                "--host is only available for --gitlab, --bitbucket and --gitea".to_string(),
+            ));
+        }
+
+        let source = if let Some(url) = git {
+            Source::Git { url, options }
+        } else if let Some(repo) = github {
+            Source::GitHub { repo, options }
+        } else if let Some(repo) = gitlab {
+            Source::GitLab {
+                repo,
+                host,
+                options,
+            }
+        } else if let Some(repo) = bitbucket {
+            Source::Bitbucket {
+                repo,
+                host,
+                options,
+            }
+        } else if let Some(repo) = gitea {
+            Source::Gitea {
+                repo,
+                host,
+                options,
+            }
+        } else {
+            unreachable!("clap requires one of the source options")
+        };
+
+        let ext_type = if self.theme {
+            ExtensionType::Theme
+        } else {
+            ExtensionType::Plugin
+        };
+
+        let extension = Extension {
+            name: self.name,
+            source,
+            hooks: None,
+        };
+        Ok((extension, ext_type))
+    }
+}
+
 impl Cli {
     pub async fn execute(self) -> Result<()> {
         // Set up logging level based on verbosity flags
@@ -63,16 +351,52 @@ impl Cli {
             std::env::set_var("RUST_LOG", "info");
         }
 
-        let command = self.command.unwrap_or(Commands::Install);
+        // Without a command, install with the default options
+        let Some(command) = self.command else {
+            return install(InstallOptions::default()).await;
+        };
 
         match command {
             Commands::Init => init().await,
-            Commands::Install => install().await,
-            Commands::Uninstall => uninstall().await,
-            Commands::Reinstall { extension } => reinstall(extension).await,
-            Commands::Update { extensions } => update(extensions).await,
+            Commands::Install(args) => install(args.into()).await,
+            Commands::Uninstall { keep_data } => uninstall(keep_data).await,
+            Commands::Reinstall { extension, force } => reinstall(extension, force).await,
+            Commands::Update {
+                extensions,
+                jobs,
+                force,
+            } => update(extensions, jobs, force).await,
+            Commands::Add(args) => {
+                let (install, keep_data) = (args.install, args.keep_data);
+                let (extension, ext_type) = args.into_extension()?;
+                add(extension, ext_type, install, keep_data).await
+            }
+            Commands::Remove {
+                name,
+                install,
+                keep_data,
+            } => remove(name, install, keep_data).await,
+            Commands::Import => import().await,
             Commands::State => state().await,
+            Commands::Check => check().await,
+            Commands::Status => status().await,
+            Commands::Doctor => doctor().await,
+            Commands::Outdated => outdated().await,
+            Commands::Switch { env, keep_data } => {
+                let options = InstallOptions {
+                    keep_data,
+                    ..Default::default()
+                };
+                switch(env, options).await
+            }
+            Commands::Envs => envs().await,
             Commands::Edit => edit().await,
+            Commands::Package { output, jobs } => package(output, jobs).await,
+            Commands::Cache { command } => match command {
+                CacheCommand::List => cache_list().await,
+                CacheCommand::Clean => cache_clean().await,
+                CacheCommand::Prune { days } => cache_prune(days).await,
+            },
             Commands::Version => {
                 println!("rex {}", env!("CARGO_PKG_VERSION"));
                 Ok(())
@@ -0,0 +1,53 @@
+use crate::commands::install::{install_selected, InstallOptions};
+use crate::commands::utils::ensure_default_env;
+use crate::config::Config;
+use crate::editor::ConfigEditor;
+use crate::error::{Result, RexerError};
+use crate::extension::{Extension, ExtensionType};
+use colored::*;
+use std::fs;
+
+/// Append an extension to .extensions.yml, keeping the rest of the file as it is
+pub async fn add(
+    extension: Extension,
+    ext_type: ExtensionType,
+    install: bool,
+    keep_data: bool,
+) -> Result<()> {
+    let config = Config::new()?;
+    let extensions_config = config.load_extensions_config()?;
+
+    if extensions_config
+        .all_extensions()
+        .any(|(ext, _)| ext.name == extension.name)
+    {
+        return Err(RexerError::InvalidConfig(format!(
+            "{} is already defined in .extensions.yml",
+            extension.name
+        )));
+    }
+    extension.source.validate()?;
+
+    if install {
+        ensure_default_env(&config, "--install")?;
+    }
+
+    let mut editor = ConfigEditor::new(&fs::read_to_string(config.extensions_file_path())?);
+    editor.add(ext_type, &extension)?;
+    config.save_extensions_file(&editor.content())?;
+
+    println!("Added {} to .extensions.yml", extension.name.blue());
+
+    if install {
+        install_selected(
+            &[extension.name],
+            InstallOptions {
+                keep_data,
+                ..Default::default()
+            },
+        )
+        .await?;
+    }
+
+    Ok(())
+}
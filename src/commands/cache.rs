@@ -0,0 +1,99 @@
+use crate::cache::{Mirror, MirrorCache};
+use crate::error::{Result, RexerError};
+use chrono::{TimeDelta, Utc};
+use colored::*;
+use std::fs;
+
+pub async fn cache_list() -> Result<()> {
+    let cache = open_cache()?;
+    let mirrors = cache.mirrors()?;
+
+    for mirror in &mirrors {
+        let last_used = mirror
+            .last_used
+            .map(|time| time.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "never".to_string());
+        println!(
+            " * {} ({}, last used {last_used})",
+            describe(mirror).blue(),
+            format_size(mirror.size)
+        );
+    }
+
+    let total: u64 = mirrors.iter().map(|mirror| mirror.size).sum();
+    println!(
+        "{} mirror(s), {} in {}",
+        mirrors.len(),
+        format_size(total),
+        cache.root().display()
+    );
+    Ok(())
+}
+
+/// Remove every mirror. Installed extensions don't depend on the cache.
+pub async fn cache_clean() -> Result<()> {
+    let cache = open_cache()?;
+    let mirrors = cache.mirrors()?;
+
+    for mirror in &mirrors {
+        fs::remove_dir_all(&mirror.path)?;
+    }
+
+    println!("Removed {} mirror(s)", mirrors.len());
+    Ok(())
+}
+
+/// Remove the mirrors not used for the given number of days, and broken mirrors
+pub async fn cache_prune(days: u64) -> Result<()> {
+    let cache = open_cache()?;
+    // A cutoff beyond the representable range keeps every mirror
+    let cutoff = i64::try_from(days)
+        .ok()
+        .and_then(TimeDelta::try_days)
+        .and_then(|age| Utc::now().checked_sub_signed(age));
+
+    let mut removed = 0;
+    for mirror in cache.mirrors()? {
+        let unused = cutoff.is_some_and(|cutoff| mirror.last_used.is_none_or(|time| time < cutoff));
+        if unused || mirror.url.is_none() {
+            println!("Removing {}", describe(&mirror).blue());
+            fs::remove_dir_all(&mirror.path)?;
+            removed += 1;
+        }
+    }
+
+    println!("Removed {removed} mirror(s) not used in {days} day(s)");
+    Ok(())
+}
+
+fn open_cache() -> Result<MirrorCache> {
+    MirrorCache::open().ok_or_else(|| {
+        RexerError::InvalidConfig(
+            "No cache directory found, set REXER_CACHE_DIR to use the cache".to_string(),
+        )
+    })
+}
+
+fn describe(mirror: &Mirror) -> String {
+    match &mirror.url {
+        Some(url) => url.clone(),
+        None => mirror.path.display().to_string(),
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,22 @@
+use crate::config::{Config, EXTENSIONS_FILE};
+use crate::error::{Result, RexerError};
+
+pub async fn check() -> Result<()> {
+    let config = Config::new()?;
+
+    match config.load_extensions_config() {
+        Ok(extensions_config) => {
+            let environments = extensions_config.env_names().len();
+            println!("{EXTENSIONS_FILE} is valid ({environments} environment(s))");
+            Ok(())
+        }
+        Err(RexerError::InvalidConfig(report)) => {
+            let count = report.lines().count();
+            eprintln!("{report}");
+            Err(RexerError::InvalidConfig(format!(
+                "{count} problem(s) found in {EXTENSIONS_FILE}"
+            )))
+        }
+        Err(e) => Err(e),
+    }
+}
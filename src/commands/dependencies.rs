@@ -0,0 +1,221 @@
+use crate::commands::hooks::HookEvent;
+use crate::commands::transaction::Transaction;
+use crate::config::Config;
+use crate::error::{Result, RexerError};
+use crate::extension::{Extension, ExtensionType, LockedExtension};
+use crate::plugin::{redmine_version, PluginInfo};
+use colored::*;
+use std::collections::HashMap;
+use std::fs;
+
+/// A plugin that will be in `plugins/` once the changes are applied
+pub struct Plugin {
+    pub name: String,
+    pub info: PluginInfo,
+    /// Whether the plugin is being installed, as opposed to staying as it is
+    pub staged: bool,
+}
+
+/// Check the `requires_redmine_plugin` and `requires_redmine` declarations of the
+/// plugins that will be installed and return the order to install the extensions in,
+/// dependencies first.
+///
+/// Unmet requirements of the staged plugins fail the install, those of plugins that
+/// stay installed are only reported.
+pub fn install_order(
+    config: &Config,
+    transaction: &Transaction<'_>,
+    to_install: &[(&Extension, ExtensionType, HookEvent)],
+    to_remove: &[&LockedExtension],
+    ignore_compat: bool,
+) -> Result<Vec<usize>> {
+    let mut plugins = Vec::new();
+    for (extension, ext_type, _) in to_install {
+        if matches!(ext_type, ExtensionType::Plugin) {
+            let staging_dir = transaction.staging_dir(*ext_type, &extension.name);
+            plugins.push(Plugin {
+                name: extension.name.clone(),
+                info: PluginInfo::read(&staging_dir)?,
+                staged: true,
+            });
+        }
+    }
+
+    for plugin in installed_plugins(config)? {
+        let replaced = plugins.iter().any(|other| other.name == plugin.name);
+        let removed = to_remove.iter().any(|locked_ext| {
+            locked_ext.name == plugin.name
+                && matches!(locked_ext.extension_type, ExtensionType::Plugin)
+        });
+        if !replaced && !removed {
+            plugins.push(plugin);
+        }
+    }
+
+    if !ignore_compat {
+        if let Some(redmine_version) = redmine_version(&config.redmine_root)? {
+            let problems: Vec<_> = plugins
+                .iter()
+                .filter(|plugin| plugin.staged)
+                .filter_map(|plugin| incompatibility(plugin, &redmine_version))
+                .collect();
+            if !problems.is_empty() {
+                return Err(RexerError::CompatibilityError(format!(
+                    "{}, use --ignore-compat to install anyway",
+                    problems.join("; ")
+                )));
+            }
+        }
+    }
+
+    check_requirements(&plugins)?;
+
+    // Plugins are referred to by their registered id, which usually is the directory name
+    let by_id: HashMap<&str, &str> = plugins
+        .iter()
+        .flat_map(|plugin| {
+            let name = plugin.name.as_str();
+            [
+                Some((name, name)),
+                plugin.info.id.as_deref().map(|id| (id, name)),
+            ]
+        })
+        .flatten()
+        .collect();
+
+    let mut order = Vec::new();
+    let mut visiting = Vec::new();
+    for index in 0..to_install.len() {
+        visit(
+            index,
+            to_install,
+            &plugins,
+            &by_id,
+            &mut visiting,
+            &mut order,
+        );
+    }
+
+    Ok(order)
+}
+
+/// The plugins currently in `plugins/`
+pub fn installed_plugins(config: &Config) -> Result<Vec<Plugin>> {
+    let plugins_dir = config.plugins_dir();
+    if !plugins_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut plugins = Vec::new();
+    for entry in fs::read_dir(&plugins_dir)? {
+        let entry = entry?;
+        if entry.path().is_dir() {
+            plugins.push(Plugin {
+                name: entry.file_name().to_string_lossy().to_string(),
+                info: PluginInfo::read(&entry.path())?,
+                staged: false,
+            });
+        }
+    }
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(plugins)
+}
+
+/// Requirements of the plugin on other plugins that are missing or have another version
+pub fn unmet_requirements(plugin: &Plugin, plugins: &[Plugin]) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    for requirement in &plugin.info.requires_plugins {
+        let required = plugins.iter().find(|other| {
+            other.info.id.as_deref() == Some(requirement.id.as_str())
+                || other.name == requirement.id
+        });
+
+        match required {
+            None => problems.push(format!(
+                "{} requires plugin {}, which is neither in .extensions.yml nor installed",
+                plugin.name, requirement.id
+            )),
+            Some(required) => match &required.info.version {
+                Some(version) if !requirement.version.matches(version) => problems.push(format!(
+                    "{} requires {} {}, found {version}",
+                    plugin.name, requirement.id, requirement.version
+                )),
+                _ => {}
+            },
+        }
+    }
+
+    problems
+}
+
+/// Why the plugin doesn't run on the given Redmine version, if it declares so
+pub fn incompatibility(plugin: &Plugin, redmine_version: &str) -> Option<String> {
+    let requirement = plugin.info.requires_redmine.as_ref()?;
+    (!requirement.matches(redmine_version)).then(|| {
+        format!(
+            "{} requires Redmine {requirement}, found {redmine_version}",
+            plugin.name
+        )
+    })
+}
+
+fn check_requirements(plugins: &[Plugin]) -> Result<()> {
+    let mut problems = Vec::new();
+
+    for plugin in plugins {
+        for problem in unmet_requirements(plugin, plugins) {
+            if plugin.staged {
+                problems.push(problem);
+            } else {
+                eprintln!("{} {problem}", "warning:".yellow());
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(RexerError::DependencyError(problems.join("; ")))
+    }
+}
+
+/// Add the extension to the order after the plugins it requires. A dependency cycle
+/// keeps the configured order.
+fn visit(
+    index: usize,
+    to_install: &[(&Extension, ExtensionType, HookEvent)],
+    plugins: &[Plugin],
+    by_id: &HashMap<&str, &str>,
+    visiting: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) {
+    if order.contains(&index) || visiting.contains(&index) {
+        return;
+    }
+    visiting.push(index);
+
+    let (extension, ext_type, _) = &to_install[index];
+    if matches!(ext_type, ExtensionType::Plugin) {
+        let requirements = plugins
+            .iter()
+            .find(|plugin| plugin.staged && plugin.name == extension.name)
+            .map(|plugin| plugin.info.requires_plugins.as_slice())
+            .unwrap_or_default();
+
+        for requirement in requirements {
+            let Some(name) = by_id.get(requirement.id.as_str()) else {
+                continue;
+            };
+            if let Some(dependency) = to_install.iter().position(|(other, other_type, _)| {
+                other.name == *name && matches!(other_type, ExtensionType::Plugin)
+            }) {
+                visit(dependency, to_install, plugins, by_id, visiting, order);
+            }
+        }
+    }
+
+    visiting.pop();
+    order.push(index);
+}
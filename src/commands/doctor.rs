@@ -0,0 +1,50 @@
+use crate::commands::dependencies::{incompatibility, installed_plugins, unmet_requirements};
+use crate::config::Config;
+use crate::error::{Result, RexerError};
+use crate::plugin::redmine_version;
+use colored::*;
+
+/// Check the installed plugins against the Redmine version and each other
+pub async fn doctor() -> Result<()> {
+    let config = Config::new()?;
+
+    let redmine_version = redmine_version(&config.redmine_root)?;
+    match &redmine_version {
+        Some(version) => println!("Redmine {version}"),
+        None => println!(
+            "Redmine version not found in lib/redmine/version.rb, skipping compatibility checks"
+        ),
+    }
+
+    let plugins = installed_plugins(&config)?;
+    let mut failing = 0;
+    for plugin in &plugins {
+        let mut problems = unmet_requirements(plugin, &plugins);
+        if let Some(version) = &redmine_version {
+            problems.extend(incompatibility(plugin, version));
+        }
+
+        let version = plugin
+            .info
+            .version
+            .as_ref()
+            .map(|version| format!(" ({version})"))
+            .unwrap_or_default();
+        let summary = if problems.is_empty() {
+            "ok".green().to_string()
+        } else {
+            failing += 1;
+            problems.join("; ").yellow().to_string()
+        };
+        println!(" * {}{version}: {summary}", plugin.name.blue());
+    }
+
+    if failing > 0 {
+        return Err(RexerError::CompatibilityError(format!(
+            "{failing} plugin(s) have problems"
+        )));
+    }
+
+    println!("No problems found in {} plugin(s)", plugins.len());
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use crate::commands::state::format_source_info;
+use crate::config::Config;
+use crate::error::Result;
+use crate::extension::ExtensionType;
+
+pub async fn envs() -> Result<()> {
+    let config = Config::new()?;
+    let extensions_config = config.load_extensions_config()?;
+
+    let current_env = config.load_lock_file()?.map(|lock_file| lock_file.env);
+
+    for env in extensions_config.env_names() {
+        if current_env.as_deref() == Some(env) {
+            println!("{env} (current)");
+        } else {
+            println!("{env}");
+        }
+
+        let env_config = extensions_config.for_env(env).unwrap_or_default();
+        for (ext, ext_type) in env_config.all_extensions() {
+            let type_name = match ext_type {
+                ExtensionType::Plugin => "plugin",
+                ExtensionType::Theme => "theme",
+            };
+            let source_info = format_source_info(&ext.source, &None);
+            println!(" * {} [{type_name}] ({source_info})", ext.name);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use crate::commands::utils::{fetch_source, FetchedSource};
+use crate::config::Config;
+use crate::error::{Result, RexerError};
+use crate::extension::Source;
+use colored::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// An extension source to fetch into a directory
+pub struct FetchJob {
+    pub source: Source,
+    pub dest_dir: PathBuf,
+    /// Printed with the outcome once the source has been fetched
+    pub message: String,
+    /// Fetched instead when the source is a locked commit that no longer exists upstream
+    pub fallback: Option<Source>,
+}
+
+/// Fetch the sources with at most `jobs` fetches running at the same time.
+/// The results are in the order of the given jobs.
+pub async fn fetch_all(
+    config: &Config,
+    fetch_jobs: Vec<FetchJob>,
+    jobs: usize,
+) -> Result<Vec<FetchedSource>> {
+    let total = fetch_jobs.len();
+
+    // Extensions sharing a repository clone are fetched one after another
+    let mut groups: Vec<Vec<(usize, FetchJob)>> = Vec::new();
+    let mut shared_groups: HashMap<String, usize> = HashMap::new();
+    for (index, job) in fetch_jobs.into_iter().enumerate() {
+        let shared_url = job
+            .source
+            .git_options()
+            .filter(|options| options.path.is_some())
+            .map(|_| job.source.full_url());
+
+        match shared_url {
+            Some(url) => {
+                let group = *shared_groups.entry(url).or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+                groups[group].push((index, job));
+            }
+            None => groups.push(vec![(index, job)]),
+        }
+    }
+
+    let config = Arc::new(config.clone());
+    let semaphore = Arc::new(Semaphore::new(jobs.max(1)));
+    let mut tasks = JoinSet::new();
+
+    for group in groups {
+        let config = Arc::clone(&config);
+        let semaphore = Arc::clone(&semaphore);
+
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+
+            let mut results = Vec::new();
+            for (index, job) in group {
+                let mut result = fetch_source(&config, &job.source, &job.dest_dir).await;
+                // Only a fetch that went through and lacks the commit falls back,
+                // network and authentication failures are reported as they are
+                if let (Err(e @ RexerError::ReferenceNotFound(_)), Some(fallback)) =
+                    (&result, &job.fallback)
+                {
+                    report_drift(&job, fallback, e);
+                    result = fetch_source(&config, fallback, &job.dest_dir).await;
+                }
+                report(&job, &result);
+                results.push((index, result));
+            }
+            results
+        });
+    }
+
+    let mut fetched: Vec<Option<FetchedSource>> = (0..total).map(|_| None).collect();
+    let mut first_error = None;
+
+    while let Some(results) = tasks.join_next().await {
+        for (index, result) in results.map_err(anyhow::Error::from)? {
+            match result {
+                Ok(source) => fetched[index] = Some(source),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+    }
+
+    if let Some(e) = first_error {
+        return Err(e);
+    }
+
+    Ok(fetched.into_iter().flatten().collect())
+}
+
+fn report_drift(job: &FetchJob, fallback: &Source, error: &RexerError) {
+    let reference = fallback
+        .reference()
+        .unwrap_or_else(|| "the default branch".to_string());
+    eprintln!(
+        "{}... {} the locked commit is not available upstream ({}), fetching {reference} instead",
+        job.message,
+        "drift:".yellow(),
+        error.to_string().lines().next().unwrap_or_default()
+    );
+}
+
+/// Print the outcome of a fetch as a single line so concurrent fetches don't interleave
+fn report(job: &FetchJob, result: &Result<FetchedSource>) {
+    match result {
+        Ok(fetched) => match &fetched.commit_hash {
+            Some(commit_hash) => {
+                println!("{}... done ({})", job.message, &commit_hash[..8]);
+            }
+            None => println!("{}... done", job.message),
+        },
+        Err(e) => eprintln!("{}... {}: {e}", job.message, "failed".red()),
+    }
+}
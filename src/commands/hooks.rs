@@ -0,0 +1,84 @@
+use crate::commands::utils::run_command;
+use crate::config::Config;
+use crate::error::{Result, RexerError};
+use crate::extension::Hooks;
+use colored::*;
+
+#[derive(Debug, Clone, Copy)]
+pub enum HookEvent {
+    Installed,
+    Uninstalled,
+    Updated,
+}
+
+impl HookEvent {
+    fn name(self) -> &'static str {
+        match self {
+            HookEvent::Installed => "installed",
+            HookEvent::Uninstalled => "uninstalled",
+            HookEvent::Updated => "updated",
+        }
+    }
+
+    fn command(self, hooks: &Hooks) -> Option<&str> {
+        match self {
+            HookEvent::Installed => hooks.installed.as_deref(),
+            HookEvent::Uninstalled => hooks.uninstalled.as_deref(),
+            HookEvent::Updated => hooks.updated.as_deref(),
+        }
+    }
+}
+
+/// Runs extension hooks and collects their failures so that a failing hook
+/// does not interrupt the remaining extensions
+pub struct HookRunner<'a> {
+    config: &'a Config,
+    failures: Vec<String>,
+}
+
+impl<'a> HookRunner<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Self {
+            config,
+            failures: Vec::new(),
+        }
+    }
+
+    pub fn run(&mut self, extension_name: &str, hooks: Option<&Hooks>, event: HookEvent) {
+        let Some(command) = hooks.and_then(|hooks| event.command(hooks)) else {
+            return;
+        };
+
+        println!(
+            "Running {} hook for {}...",
+            event.name(),
+            extension_name.blue()
+        );
+
+        // Hooks run from the Redmine root through the configured command prefix
+        if let Err(e) = run_command(
+            "sh",
+            &["-c", command],
+            Some(&self.config.redmine_root),
+            self.config,
+        ) {
+            eprintln!(
+                "{} {} hook for {}: {e}",
+                "Failed".red(),
+                event.name(),
+                extension_name
+            );
+            self.failures
+                .push(format!("{} ({})", extension_name, event.name()));
+        }
+    }
+
+    /// Return an error listing every extension whose hook failed
+    pub fn finish(self) -> Result<()> {
+        if self.failures.is_empty() {
+            Ok(())
+        } else {
+            Err(RexerError::HookError(self.failures.join(", ")))
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::commands::state::format_source_info;
+use crate::commands::utils::ensure_default_env;
+use crate::config::Config;
+use crate::editor::ConfigEditor;
+use crate::error::Result;
+use crate::extension::{
+    Extension, ExtensionType, ExtensionsConfig, GitOptions, LockFile, LockedExtension, Source,
+    DEFAULT_ENV,
+};
+use crate::git::GitManager;
+use chrono::Utc;
+use colored::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Bring plugins and themes cloned by hand under rex management, recording their
+/// current commits without cloning them again
+pub async fn import() -> Result<()> {
+    let config = Config::new()?;
+    // Imported extensions go to the top-level lists, so another environment would remove them
+    ensure_default_env(&config, "rex import")?;
+
+    let (extensions_config, content) = if config.extensions_file_path().exists() {
+        (
+            config.load_extensions_config()?,
+            fs::read_to_string(config.extensions_file_path())?,
+        )
+    } else {
+        (ExtensionsConfig::default(), String::new())
+    };
+    let mut editor = ConfigEditor::new(&content);
+    let mut lock_file = config.load_lock_file()?.unwrap_or_else(|| LockFile {
+        env: DEFAULT_ENV.to_string(),
+        extensions: Vec::new(),
+    });
+
+    let mut imported = 0;
+    for (ext_type, dir) in [
+        (ExtensionType::Plugin, config.plugins_dir()),
+        (ExtensionType::Theme, config.themes_dir()),
+    ] {
+        for (name, path) in unmanaged_dirs(&dir, &extensions_config, &lock_file)? {
+            let Some((source, commit_hash)) = detect_source(&path)? else {
+                eprintln!(
+                    "Skipping {}: not a git repository with a remote",
+                    name.blue()
+                );
+                continue;
+            };
+
+            println!(
+                "Importing {} ({})",
+                name.blue(),
+                format_source_info(&source, &Some(commit_hash.clone()))
+            );
+
+            let extension = Extension {
+                name: name.clone(),
+                source: source.clone(),
+                hooks: None,
+            };
+            editor.add(ext_type, &extension)?;
+
+            lock_file.extensions.push(LockedExtension {
+                name,
+                extension_type: ext_type,
+                source,
+                commit_hash: Some(commit_hash),
+                installed_at: Utc::now().to_rfc3339(),
+                hooks: None,
+                resolved_path: None,
+                digest: None,
+                resolved_tag: None,
+                submodule_commits: BTreeMap::new(),
+            });
+            imported += 1;
+        }
+    }
+
+    if imported == 0 {
+        println!("No unmanaged extensions found");
+        return Ok(());
+    }
+
+    config.save_extensions_file(&editor.content())?;
+    config.save_lock_file(&lock_file)?;
+
+    println!("Imported {imported} extension(s)");
+    Ok(())
+}
+
+/// Directories not yet listed in the configuration or the lock file, sorted by name
+fn unmanaged_dirs(
+    dir: &Path,
+    extensions_config: &ExtensionsConfig,
+    lock_file: &LockFile,
+) -> Result<Vec<(String, PathBuf)>> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        let managed = extensions_config
+            .all_extensions()
+            .any(|(ext, _)| ext.name == name)
+            || lock_file.extensions.iter().any(|ext| ext.name == name);
+        if name.starts_with('.') || managed || !entry.file_type()?.is_dir() {
+            continue;
+        }
+
+        dirs.push((name, entry.path()));
+    }
+    dirs.sort();
+
+    Ok(dirs)
+}
+
+/// Source and commit of a cloned repository, following its checked out branch or tag
+fn detect_source(path: &Path) -> Result<Option<(Source, String)>> {
+    if !path.join(".git").exists() {
+        return Ok(None);
+    }
+    let Some(url) = GitManager::remote_url(path)? else {
+        return Ok(None);
+    };
+    let commit_hash = GitManager::get_current_commit_hash(path)?;
+
+    let mut options = GitOptions::default();
+    if let Some(branch) = GitManager::current_branch(path) {
+        options.branch = Some(branch);
+    } else if let Some(tag) = GitManager::current_tag(path) {
+        options.tag = Some(tag);
+    } else {
+        options.commit = Some(commit_hash.clone());
+    }
+
+    let source = match github_repo(&url) {
+        Some(repo) => Source::GitHub { repo, options },
+        None => Source::Git { url, options },
+    };
+
+    Ok(Some((source, commit_hash)))
+}
+
+/// `owner/repo` of a GitHub clone URL
+fn github_repo(url: &str) -> Option<String> {
+    let path = [
+        "https://github.com/",
+        "git@github.com:",
+        "ssh://git@github.com/",
+    ]
+    .iter()
+    .find_map(|prefix| url.strip_prefix(prefix))?;
+    let repo = path.trim_end_matches('/').trim_end_matches(".git");
+
+    (repo.split('/').count() == 2).then(|| repo.to_string())
+}
@@ -1,24 +1,198 @@
-use crate::config::Config;
-use crate::error::Result;
-use crate::extension::{Extension, ExtensionType, LockFile, LockedExtension, Source};
+use crate::cache::offline_location;
+use crate::commands::dependencies::install_order;
+use crate::commands::fetch::{fetch_all, FetchJob};
+use crate::commands::hooks::{HookEvent, HookRunner};
+use crate::commands::package::{package_path, PackageManifest};
+use crate::commands::plan::InstallPlan;
+use crate::commands::transaction::Transaction;
+use crate::commands::utils::{
+    lock_entry, matches_lock, rollback_plugin_migrations, run_plugin_setup,
+};
+use crate::config::{Config, DEFAULT_JOBS};
+use crate::error::{Result, RexerError};
+use crate::extension::{
+    Extension, ExtensionType, ExtensionsConfig, LockFile, LockedExtension, Source, Submodules,
+    DEFAULT_ENV,
+};
 use crate::git::GitManager;
 use chrono::Utc;
 use colored::*;
 use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct InstallOptions {
+    /// Keep the database tables of removed plugins instead of rolling back their migrations
+    pub keep_data: bool,
+    /// Only print what would be done
+    pub dry_run: bool,
+    /// Print the dry run plan as JSON
+    pub json: bool,
+    /// Number of extensions fetched concurrently
+    pub jobs: usize,
+    /// Install exactly the commits in the lock file, failing if the configuration changed
+    pub frozen: bool,
+    /// Install plugins whose `requires_redmine` excludes the installed Redmine
+    pub ignore_compat: bool,
+    /// Install the lock file from the mirror cache without contacting any remote
+    pub offline: bool,
+    /// Install the extensions of a tarball written by rex package instead of fetching them
+    pub from_package: Option<PathBuf>,
+}
 
-pub async fn install() -> Result<()> {
-    let config = Config::new()?;
+impl Default for InstallOptions {
+    fn default() -> Self {
+        Self {
+            keep_data: false,
+            dry_run: false,
+            json: false,
+            jobs: DEFAULT_JOBS,
+            frozen: false,
+            ignore_compat: false,
+            offline: false,
+            from_package: None,
+        }
+    }
+}
+
+pub async fn install(options: InstallOptions) -> Result<()> {
+    let mut config = Config::new()?;
+    config.offline = options.offline;
     let extensions_config = config.load_extensions_config()?;
 
     let current_lock = config.load_lock_file()?;
 
+    if let Some(package) = &options.from_package {
+        return install_package(
+            &config,
+            &extensions_config,
+            current_lock.as_ref(),
+            package,
+            &options,
+        )
+        .await;
+    }
+
+    // Stay on the environment recorded in the lock file
+    let env = current_lock
+        .as_ref()
+        .map(|lock_file| lock_file.env.clone())
+        .unwrap_or_else(|| DEFAULT_ENV.to_string());
+
+    install_env(
+        &config,
+        &extensions_config,
+        &env,
+        current_lock.as_ref(),
+        &options,
+    )
+    .await
+}
+
+/// Install the extensions of the given environment, replacing the current installation
+pub async fn install_env(
+    config: &Config,
+    extensions_config: &ExtensionsConfig,
+    env: &str,
+    current_lock: Option<&LockFile>,
+    options: &InstallOptions,
+) -> Result<()> {
+    let env_config = extensions_config
+        .for_env(env)
+        .ok_or_else(|| RexerError::EnvironmentNotFound(env.to_string()))?;
+
+    install_resolved(config, &env_config, env, current_lock, options).await
+}
+
+/// Apply the configuration of only the named extensions of the current environment.
+/// Every other extension stays as the lock file records it.
+pub async fn install_selected(names: &[String], options: InstallOptions) -> Result<()> {
+    let config = Config::new()?;
+    let extensions_config = config.load_extensions_config()?;
+    let current_lock = config.load_lock_file()?;
+
+    let env = current_lock
+        .as_ref()
+        .map(|lock_file| lock_file.env.clone())
+        .unwrap_or_else(|| DEFAULT_ENV.to_string());
+    let env_config = extensions_config
+        .for_env(&env)
+        .ok_or_else(|| RexerError::EnvironmentNotFound(env.clone()))?;
+
+    let mut selected_config = ExtensionsConfig::default();
+    let installed = current_lock
+        .iter()
+        .flat_map(|lock_file| &lock_file.extensions)
+        .filter(|locked_ext| !names.contains(&locked_ext.name))
+        .map(|locked_ext| {
+            let extension = Extension {
+                name: locked_ext.name.clone(),
+                source: locked_ext.source.clone(),
+                hooks: locked_ext.hooks.clone(),
+            };
+            (extension, locked_ext.extension_type)
+        });
+    let configured = env_config
+        .all_extensions()
+        .filter(|(extension, _)| names.contains(&extension.name))
+        .map(|(extension, ext_type)| (extension.clone(), ext_type));
+
+    for (extension, ext_type) in installed.chain(configured) {
+        match ext_type {
+            ExtensionType::Plugin => selected_config.plugins.push(extension),
+            ExtensionType::Theme => selected_config.themes.push(extension),
+        }
+    }
+
+    install_resolved(
+        &config,
+        &selected_config,
+        &env,
+        current_lock.as_ref(),
+        &options,
+    )
+    .await
+}
+
+async fn install_resolved(
+    config: &Config,
+    env_config: &ExtensionsConfig,
+    env: &str,
+    current_lock: Option<&LockFile>,
+    options: &InstallOptions,
+) -> Result<()> {
+    if options.dry_run {
+        // Without a lock file every extension is new
+        let empty_lock = LockFile {
+            env: env.to_string(),
+            extensions: Vec::new(),
+        };
+        let lock_file = current_lock.unwrap_or(&empty_lock);
+
+        let diff = calculate_diff(env_config, lock_file);
+        let to_restore = missing_extensions(config, lock_file, &diff);
+        let plan = InstallPlan::new(config, env, lock_file, &diff, &to_restore, options)?;
+        return plan.print(options.json);
+    }
+
+    // Offline, only the commits recorded in the lock file can be installed
+    if options.frozen || options.offline {
+        let lock_file = current_lock.ok_or_else(|| {
+            RexerError::LockFileError(format!(
+                "--{} requires .extensions.lock",
+                frozen_flag(options)
+            ))
+        })?;
+        return install_frozen(config, env_config, lock_file, options).await;
+    }
+
     // Determine what needs to be done
-    if let Some(lock_file) = &current_lock {
+    if let Some(lock_file) = current_lock {
         // Update existing installation
-        update_installation(&config, &extensions_config, lock_file).await?;
+        update_installation(config, env_config, env, lock_file, options).await?;
     } else {
         // Fresh install
-        install_all_extensions(&config, &extensions_config).await?;
+        install_all_extensions(config, env_config, env, options).await?;
     }
 
     Ok(())
@@ -26,120 +200,641 @@ pub async fn install() -> Result<()> {
 
 async fn install_all_extensions(
     config: &Config,
-    extensions_config: &crate::extension::ExtensionsConfig,
+    extensions_config: &ExtensionsConfig,
+    env: &str,
+    options: &InstallOptions,
 ) -> Result<()> {
-    let mut locked_extensions = Vec::new();
-
-    for (extension, ext_type) in extensions_config.all_extensions() {
-        println!("Installing {}...", extension.name.blue());
-        let commit_hash = install_extension(config, extension, ext_type).await?;
-
-        locked_extensions.push(LockedExtension {
-            name: extension.name.clone(),
-            extension_type: ext_type,
-            source: extension.source.clone(),
-            commit_hash: Some(commit_hash),
-            installed_at: Utc::now().to_rfc3339(),
+    let to_install: Vec<_> = extensions_config
+        .all_extensions()
+        .map(|(extension, ext_type)| (extension, ext_type, HookEvent::Installed))
+        .collect();
+
+    let mut transaction = Transaction::begin(config)?;
+    let result = apply_changes(config, &mut transaction, &to_install, &[], &[], options)
+        .await
+        .and_then(|locked_extensions| {
+            let lock_file = LockFile {
+                env: env.to_string(),
+                extensions: locked_extensions,
+            };
+            config.save_lock_file(&lock_file)?;
+            Ok(lock_file)
         });
-    }
-
-    let lock_file = LockFile {
-        extensions: locked_extensions,
-    };
+    let lock_file = finish_transaction(transaction, result)?;
 
-    config.save_lock_file(&lock_file)?;
     println!("Installed {} extensions", lock_file.extensions.len());
 
-    Ok(())
+    run_hooks(config, &to_install, &[])
 }
 
 async fn update_installation(
     config: &Config,
-    extensions_config: &crate::extension::ExtensionsConfig,
+    extensions_config: &ExtensionsConfig,
+    env: &str,
     lock_file: &LockFile,
+    options: &InstallOptions,
 ) -> Result<()> {
     let diff = calculate_diff(extensions_config, lock_file);
+    let to_restore = missing_extensions(config, lock_file, &diff);
+
+    // New extensions are installed, extensions whose source changed are replaced
+    let to_install: Vec<_> = diff
+        .added
+        .iter()
+        .map(|(extension, ext_type)| (*extension, *ext_type, HookEvent::Installed))
+        .chain(
+            diff.source_changed
+                .iter()
+                .map(|(extension, ext_type, _)| (*extension, *ext_type, HookEvent::Updated)),
+        )
+        .chain(
+            extensions_config
+                .all_extensions()
+                .filter_map(|(extension, ext_type)| {
+                    to_restore
+                        .iter()
+                        .any(|le| le.name == extension.name)
+                        .then_some((extension, ext_type, HookEvent::Installed))
+                }),
+        )
+        .collect();
+
+    let mut transaction = Transaction::begin(config)?;
+    let result = apply_changes(
+        config,
+        &mut transaction,
+        &to_install,
+        &to_restore,
+        &diff.removed,
+        options,
+    )
+    .await
+    .and_then(|mut installed_extensions| {
+        // Build new lock file with updated state
+        let mut final_extensions = Vec::new();
+
+        // Add unchanged extensions, picking up hooks and clone modes edited in the configuration
+        for locked_ext in &lock_file.extensions {
+            // Restored extensions keep their place in the lock file
+            if to_restore.iter().any(|le| le.name == locked_ext.name) {
+                if let Some(index) = installed_extensions
+                    .iter()
+                    .position(|installed| installed.name == locked_ext.name)
+                {
+                    final_extensions.push(installed_extensions.remove(index));
+                }
+                continue;
+            }
 
-    // Install new extensions
-    let mut new_locked_extensions = Vec::new();
-    for (extension, ext_type) in &diff.added {
-        println!("Installing {}...", extension.name.blue());
-        let commit_hash = install_extension(config, extension, *ext_type).await?;
-        new_locked_extensions.push(LockedExtension {
-            name: extension.name.clone(),
-            extension_type: *ext_type,
-            source: extension.source.clone(),
-            commit_hash: Some(commit_hash),
-            installed_at: Utc::now().to_rfc3339(),
-        });
+            if !diff.removed.iter().any(|r| r.name == locked_ext.name)
+                && !diff
+                    .source_changed
+                    .iter()
+                    .any(|(_, _, old)| old.name == locked_ext.name)
+            {
+                let mut locked_ext = locked_ext.clone();
+                if let Some((extension, _)) = extensions_config
+                    .all_extensions()
+                    .find(|(ext, _)| ext.name == locked_ext.name)
+                {
+                    locked_ext.hooks = extension.hooks.clone();
+                    // The next update fetches with the new mode, see sources_equal
+                    if let (Some(locked_options), Some(options)) = (
+                        locked_ext.source.git_options_mut(),
+                        extension.source.git_options(),
+                    ) {
+                        locked_options.clone_mode = options.clone_mode;
+                    }
+                }
+                final_extensions.push(locked_ext);
+            }
+        }
+
+        // Add new and updated extensions
+        final_extensions.extend(installed_extensions);
+
+        let updated_lock = LockFile {
+            env: env.to_string(),
+            extensions: final_extensions,
+        };
+        config.save_lock_file(&updated_lock)?;
+        Ok(())
+    });
+    finish_transaction(transaction, result)?;
+
+    if diff.is_empty() && to_restore.is_empty() && lock_file.env == env {
+        println!("Extensions are up to date");
+    } else {
+        println!("Installation updated successfully");
     }
 
-    // Update extensions where source changed
-    let mut updated_locked_extensions = Vec::new();
-    for (extension, ext_type, old_locked) in &diff.source_changed {
-        println!("Updating {} (source changed)...", extension.name.blue());
-        // Uninstall old version first
-        uninstall_extension(config, old_locked).await?;
-        // Install new version
-        let commit_hash = install_extension(config, extension, *ext_type).await?;
-        updated_locked_extensions.push(LockedExtension {
-            name: extension.name.clone(),
-            extension_type: *ext_type,
-            source: extension.source.clone(),
-            commit_hash: Some(commit_hash),
-            installed_at: Utc::now().to_rfc3339(),
-        });
+    run_hooks(config, &to_install, &diff.removed)
+}
+
+/// Unchanged extensions whose directory is missing, e.g. on a fresh checkout.
+/// They are restored at the commit of their lock entry.
+fn missing_extensions<'a>(
+    config: &Config,
+    lock_file: &'a LockFile,
+    diff: &InstallDiff,
+) -> Vec<&'a LockedExtension> {
+    lock_file
+        .extensions
+        .iter()
+        .filter(|locked_ext| {
+            !diff.removed.iter().any(|r| r.name == locked_ext.name)
+                && !diff
+                    .source_changed
+                    .iter()
+                    .any(|(_, _, old)| old.name == locked_ext.name)
+        })
+        .filter(|locked_ext| {
+            let dest_dir = config.extension_dir(locked_ext.extension_type, &locked_ext.name);
+            fs::symlink_metadata(dest_dir).is_err()
+        })
+        .collect()
+}
+
+/// Install the commits and contents recorded in the lock file without resolving
+/// branches, tags or versions. Extensions already matching the lock are left alone.
+async fn install_frozen(
+    config: &Config,
+    extensions_config: &ExtensionsConfig,
+    lock_file: &LockFile,
+    options: &InstallOptions,
+) -> Result<()> {
+    let diff = calculate_diff(extensions_config, lock_file);
+    if !diff.is_empty() {
+        return Err(RexerError::LockFileError(format!(
+            ".extensions.lock does not match .extensions.yml ({}), run rex install without --{}",
+            diff.summary(),
+            frozen_flag(options)
+        )));
     }
 
-    // Uninstall removed extensions
-    for locked_ext in &diff.removed {
-        println!("Uninstalling {}...", locked_ext.name.blue());
-        uninstall_extension(config, locked_ext).await?;
+    let mut pinned = Vec::new();
+    for locked_ext in &lock_file.extensions {
+        if !matches_lock(config, locked_ext)? {
+            let extension = Extension {
+                name: locked_ext.name.clone(),
+                source: locked_ext.pinned_source(),
+                hooks: locked_ext.hooks.clone(),
+            };
+            pinned.push((extension, locked_ext.extension_type));
+        }
     }
 
-    // Build new lock file with updated state
-    let mut final_extensions = Vec::new();
+    if pinned.is_empty() {
+        println!("Extensions are up to date");
+        return Ok(());
+    }
+    if options.offline {
+        ensure_available_offline(config, &pinned)?;
+    }
 
-    // Add unchanged extensions
-    for locked_ext in &lock_file.extensions {
-        if !diff.removed.iter().any(|r| r.name == locked_ext.name)
-            && !diff
-                .source_changed
-                .iter()
-                .any(|(_, _, old)| old.name == locked_ext.name)
-        {
-            final_extensions.push(locked_ext.clone());
+    let to_install: Vec<_> = pinned
+        .iter()
+        .map(|(extension, ext_type)| (extension, *ext_type, HookEvent::Installed))
+        .collect();
+
+    let mut transaction = Transaction::begin(config)?;
+    let result = apply_changes(config, &mut transaction, &to_install, &[], &[], options)
+        .await
+        .and_then(|installed_extensions| verify_frozen(lock_file, &installed_extensions));
+    finish_transaction(transaction, result)?;
+
+    println!(
+        "Installed {} extensions from the lock file",
+        to_install.len()
+    );
+
+    run_hooks(config, &to_install, &[])
+}
+
+fn frozen_flag(options: &InstallOptions) -> &'static str {
+    if options.offline {
+        "offline"
+    } else {
+        "frozen"
+    }
+}
+
+/// Fail with the list of extensions whose locked content can't be installed without
+/// the network, before anything is fetched
+fn ensure_available_offline(config: &Config, pinned: &[(Extension, ExtensionType)]) -> Result<()> {
+    let mut missing = Vec::new();
+    let mut unsupported = 0;
+
+    for (extension, _) in pinned {
+        let problem = match &extension.source {
+            Source::Archive { url, .. } if !url.starts_with("file://") => {
+                Some(format!("archive {url} has to be downloaded"))
+            }
+            Source::Path { .. } | Source::Archive { .. } => None,
+            source
+                if source
+                    .git_options()
+                    .is_some_and(|options| options.submodules != Submodules::Off) =>
+            {
+                // Submodules are not mirrored, they always come from their own remotes
+                unsupported += 1;
+                Some(
+                    "extensions with submodules can't be installed offline, use rex package instead"
+                        .to_string(),
+                )
+            }
+            source => {
+                let url = source.full_url();
+                let commit = source
+                    .git_options()
+                    .and_then(|options| options.commit.as_deref())
+                    .unwrap_or_default();
+
+                // Extensions in a subdirectory may also be in the repositories directory
+                let repository = config
+                    .repositories_dir()
+                    .join(GitManager::repository_dir_name(&url));
+                let available = offline_location(&url)
+                    .is_some_and(|location| GitManager::has_commit(Path::new(&location), commit))
+                    || (repository.is_dir() && GitManager::has_commit(&repository, commit));
+
+                (!available).then(|| {
+                    format!(
+                        "commit {} of {url} is not in the mirror cache",
+                        &commit[..commit.len().min(8)]
+                    )
+                })
+            }
+        };
+
+        if let Some(problem) = problem {
+            missing.push(format!(" * {}: {problem}", extension.name));
         }
     }
 
-    // Add new and updated extensions
-    final_extensions.extend(new_locked_extensions);
-    final_extensions.extend(updated_locked_extensions);
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!("{}", missing.join("\n"));
+    let hint = if unsupported < missing.len() {
+        ", run rex install with network access first"
+    } else {
+        ""
+    };
+    Err(RexerError::OfflineError(format!(
+        "{} extension(s) can't be installed offline{hint}",
+        missing.len()
+    )))
+}
 
-    let updated_lock = LockFile {
-        extensions: final_extensions,
+/// Install the extensions exported by rex package instead of fetching them. Like a
+/// frozen install, the package has to match .extensions.yml and extensions already
+/// matching it are left alone.
+async fn install_package(
+    config: &Config,
+    extensions_config: &ExtensionsConfig,
+    current_lock: Option<&LockFile>,
+    package: &Path,
+    options: &InstallOptions,
+) -> Result<()> {
+    let package_dir = tempfile::Builder::new()
+        .prefix(".rexer-package")
+        .tempdir_in(&config.redmine_root)?;
+    let manifest = PackageManifest::extract(package, package_dir.path())?;
+
+    let env_config = extensions_config
+        .for_env(&manifest.env)
+        .ok_or_else(|| RexerError::EnvironmentNotFound(manifest.env.clone()))?;
+    let package_lock = LockFile {
+        env: manifest.env,
+        extensions: manifest.extensions,
     };
+    let diff = calculate_diff(&env_config, &package_lock);
+    if !diff.is_empty() {
+        return Err(RexerError::PackageError(format!(
+            "{} does not match .extensions.yml ({}), build it again with rex package",
+            package.display(),
+            diff.summary()
+        )));
+    }
+
+    // With --frozen or --offline the package has to hold exactly what the lock file records
+    if options.frozen || options.offline {
+        let lock_file = current_lock.ok_or_else(|| {
+            RexerError::LockFileError(format!(
+                "--{} requires .extensions.lock",
+                frozen_flag(options)
+            ))
+        })?;
+        if !package_matches_lock(&package_lock, lock_file) {
+            return Err(RexerError::PackageError(format!(
+                "{} does not match .extensions.lock, build it again with rex package",
+                package.display()
+            )));
+        }
+    }
+
+    let mut changed = Vec::new();
+    for locked_ext in &package_lock.extensions {
+        if !matches_lock(config, locked_ext)? {
+            let extension = Extension {
+                name: locked_ext.name.clone(),
+                source: locked_ext.source.clone(),
+                hooks: locked_ext.hooks.clone(),
+            };
+            changed.push((extension, locked_ext.extension_type));
+        }
+    }
+    let to_install: Vec<_> = changed
+        .iter()
+        .map(|(extension, ext_type)| (extension, *ext_type, HookEvent::Installed))
+        .collect();
+    let to_remove: Vec<_> = current_lock
+        .iter()
+        .flat_map(|lock_file| &lock_file.extensions)
+        .filter(|locked_ext| {
+            !package_lock
+                .extensions
+                .iter()
+                .any(|packaged| packaged.name == locked_ext.name)
+        })
+        .collect();
+
+    // Unchanged extensions keep their install time
+    let mut lock_file = package_lock.clone();
+    for locked_ext in &mut lock_file.extensions {
+        let installed = to_install
+            .iter()
+            .any(|(extension, _, _)| extension.name == locked_ext.name);
+        let current = current_lock
+            .iter()
+            .flat_map(|lock_file| &lock_file.extensions)
+            .find(|current| current.name == locked_ext.name);
+        locked_ext.installed_at = match current {
+            Some(current) if !installed => current.installed_at.clone(),
+            _ => Utc::now().to_rfc3339(),
+        };
+    }
+
+    let mut transaction = Transaction::begin(config)?;
+    let result = async {
+        for (extension, ext_type, _) in &to_install {
+            println!("Installing {} from the package", extension.name.blue());
+            let staging_dir = transaction.staging_dir(*ext_type, &extension.name);
+            if let Some(parent) = staging_dir.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(
+                package_dir
+                    .path()
+                    .join(package_path(*ext_type, &extension.name)),
+                staging_dir,
+            )?;
+        }
 
-    config.save_lock_file(&updated_lock)?;
+        install_staged(config, &mut transaction, &to_install, &to_remove, options).await?;
+        config.save_lock_file(&lock_file)
+    }
+    .await;
+    finish_transaction(transaction, result)?;
 
-    if diff.added.is_empty() && diff.removed.is_empty() && diff.source_changed.is_empty() {
+    if to_install.is_empty() && to_remove.is_empty() {
         println!("Extensions are up to date");
     } else {
-        println!("Installation updated successfully");
+        println!(
+            "Installed {} extensions from {}",
+            to_install.len(),
+            package.display()
+        );
+    }
+
+    run_hooks(config, &to_install, &to_remove)
+}
+
+/// Whether a package holds the same extensions at the same commits as the lock file
+fn package_matches_lock(package_lock: &LockFile, lock_file: &LockFile) -> bool {
+    package_lock.env == lock_file.env
+        && package_lock.extensions.len() == lock_file.extensions.len()
+        && package_lock.extensions.iter().all(|packaged| {
+            lock_file.extensions.iter().any(|locked| {
+                locked.name == packaged.name
+                    && locked.commit_hash == packaged.commit_hash
+                    && locked.submodule_commits == packaged.submodule_commits
+                    && sources_equal(&locked.source, &packaged.source)
+            })
+        })
+}
+
+/// Fail when a fetched extension differs from what the lock file records
+pub fn verify_frozen(lock_file: &LockFile, installed_extensions: &[LockedExtension]) -> Result<()> {
+    for installed in installed_extensions {
+        let Some(locked) = lock_file
+            .extensions
+            .iter()
+            .find(|locked| locked.name == installed.name)
+        else {
+            continue;
+        };
+
+        let commit_differs =
+            locked.commit_hash.is_some() && installed.commit_hash != locked.commit_hash;
+        let digest_differs = locked.digest.is_some() && installed.digest != locked.digest;
+        if commit_differs || digest_differs {
+            return Err(RexerError::LockFileError(format!(
+                "{} differs from the content recorded in .extensions.lock",
+                installed.name
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetch the extensions to install into the staging directory, then swap them into
+/// place and take the removed extensions out. Extensions in `to_restore` are fetched
+/// at the commit of their lock entry. Returns the lock entries of the installed
+/// extensions.
+async fn apply_changes(
+    config: &Config,
+    transaction: &mut Transaction<'_>,
+    to_install: &[(&Extension, ExtensionType, HookEvent)],
+    to_restore: &[&LockedExtension],
+    to_remove: &[&LockedExtension],
+    options: &InstallOptions,
+) -> Result<Vec<LockedExtension>> {
+    // Fetch everything first so a failed clone leaves the installed extensions untouched
+    let fetch_jobs = to_install
+        .iter()
+        .map(|(extension, ext_type, event)| {
+            let dest_dir = transaction.staging_dir(*ext_type, &extension.name);
+
+            // Missing extensions are restored at their locked commit like a fresh checkout
+            if let Some(locked_ext) = to_restore.iter().find(|le| le.name == extension.name) {
+                let commit = locked_ext.commit_hash.as_deref().unwrap_or_default();
+                return FetchJob {
+                    source: locked_ext.pinned_source(),
+                    dest_dir,
+                    message: format!(
+                        "Restoring {} at {}",
+                        extension.name.blue(),
+                        &commit[..commit.len().min(8)]
+                    ),
+                    fallback: extension
+                        .source
+                        .git_options()
+                        .map(|_| extension.source.clone()),
+                };
+            }
+
+            FetchJob {
+                source: extension.source.clone(),
+                dest_dir,
+                message: if matches!(event, HookEvent::Updated) {
+                    format!("Updating {} (source changed)", extension.name.blue())
+                } else {
+                    format!("Installing {}", extension.name.blue())
+                },
+                fallback: None,
+            }
+        })
+        .collect();
+    let fetched = fetch_all(config, fetch_jobs, options.jobs).await?;
+
+    let locked_extensions = to_install
+        .iter()
+        .zip(fetched)
+        .map(|((extension, ext_type, _), fetched)| lock_entry(extension, *ext_type, fetched))
+        .collect();
+
+    install_staged(config, transaction, to_install, to_remove, options).await?;
+
+    Ok(locked_extensions)
+}
+
+/// Move the staged extensions into place, setting up plugins after the plugins they
+/// require, and take the removed extensions out
+async fn install_staged(
+    config: &Config,
+    transaction: &mut Transaction<'_>,
+    to_install: &[(&Extension, ExtensionType, HookEvent)],
+    to_remove: &[&LockedExtension],
+    options: &InstallOptions,
+) -> Result<()> {
+    // Plugins are set up after the plugins they require
+    let order = install_order(
+        config,
+        transaction,
+        to_install,
+        to_remove,
+        options.ignore_compat,
+    )?;
+    for index in order {
+        let (extension, ext_type, _) = &to_install[index];
+        let dest_dir = transaction.install(*ext_type, &extension.name)?;
+
+        // For plugins, run bundle install and migrations if applicable
+        if matches!(ext_type, ExtensionType::Plugin) {
+            run_plugin_setup(&dest_dir, config).await?;
+        }
+    }
+
+    for locked_ext in to_remove {
+        println!("Uninstalling {}...", locked_ext.name.blue());
+
+        if !options.keep_data && matches!(locked_ext.extension_type, ExtensionType::Plugin) {
+            let dest_dir = config.extension_dir(locked_ext.extension_type, &locked_ext.name);
+            rollback_plugin_migrations(&dest_dir, config).await?;
+        }
+        transaction.remove(locked_ext.extension_type, &locked_ext.name)?;
     }
 
     Ok(())
 }
 
+/// Commit the transaction on success, otherwise restore the previous state
+fn finish_transaction<T>(transaction: Transaction<'_>, result: Result<T>) -> Result<T> {
+    match result {
+        Ok(value) => {
+            transaction.commit();
+            Ok(value)
+        }
+        Err(e) => {
+            eprintln!("{} restoring the previous installation", "Failed,".red());
+            if let Err(rollback_error) = transaction.rollback() {
+                eprintln!("Failed to restore the previous installation: {rollback_error}");
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Run the hooks of the installed, updated and removed extensions once all changes are in place
+fn run_hooks(
+    config: &Config,
+    installed: &[(&Extension, ExtensionType, HookEvent)],
+    removed: &[&LockedExtension],
+) -> Result<()> {
+    let mut hooks = HookRunner::new(config);
+
+    for (extension, _, event) in installed {
+        hooks.run(&extension.name, extension.hooks.as_ref(), *event);
+    }
+    for locked_ext in removed {
+        hooks.run(
+            &locked_ext.name,
+            locked_ext.hooks.as_ref(),
+            HookEvent::Uninstalled,
+        );
+    }
+
+    hooks.finish()
+}
+
 #[derive(Debug)]
-struct InstallDiff<'a> {
-    added: Vec<(&'a Extension, ExtensionType)>,
-    removed: Vec<&'a LockedExtension>,
-    source_changed: Vec<(&'a Extension, ExtensionType, &'a LockedExtension)>,
+pub struct InstallDiff<'a> {
+    pub added: Vec<(&'a Extension, ExtensionType)>,
+    pub removed: Vec<&'a LockedExtension>,
+    pub source_changed: Vec<(&'a Extension, ExtensionType, &'a LockedExtension)>,
+}
+
+impl InstallDiff<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.source_changed.is_empty()
+    }
+
+    /// Names of the added, removed and changed extensions
+    pub fn summary(&self) -> String {
+        let names = |names: Vec<&str>| names.join(", ");
+        let mut parts = Vec::new();
+
+        if !self.added.is_empty() {
+            let added = self
+                .added
+                .iter()
+                .map(|(ext, _)| ext.name.as_str())
+                .collect();
+            parts.push(format!("added: {}", names(added)));
+        }
+        if !self.removed.is_empty() {
+            let removed = self.removed.iter().map(|ext| ext.name.as_str()).collect();
+            parts.push(format!("removed: {}", names(removed)));
+        }
+        if !self.source_changed.is_empty() {
+            let changed = self
+                .source_changed
+                .iter()
+                .map(|(ext, _, _)| ext.name.as_str())
+                .collect();
+            parts.push(format!("changed: {}", names(changed)));
+        }
+
+        parts.join("; ")
+    }
 }
 
-fn calculate_diff<'a>(
-    extensions_config: &'a crate::extension::ExtensionsConfig,
+pub fn calculate_diff<'a>(
+    extensions_config: &'a ExtensionsConfig,
     lock_file: &'a LockFile,
 ) -> InstallDiff<'a> {
     let mut added = Vec::new();
@@ -181,74 +876,15 @@ fn calculate_diff<'a>(
 }
 
 fn sources_equal(source1: &Source, source2: &Source) -> bool {
-    // Compare sources to see if they're effectively the same
-    match (source1, source2) {
-        (
-            Source::Git {
-                url: url1,
-                branch: b1,
-                tag: t1,
-                commit: c1,
-            },
-            Source::Git {
-                url: url2,
-                branch: b2,
-                tag: t2,
-                commit: c2,
-            },
-        ) => url1 == url2 && b1 == b2 && t1 == t2 && c1 == c2,
-        (
-            Source::GitHub {
-                repo: r1,
-                branch: b1,
-                tag: t1,
-                commit: c1,
-            },
-            Source::GitHub {
-                repo: r2,
-                branch: b2,
-                tag: t2,
-                commit: c2,
-            },
-        ) => r1 == r2 && b1 == b2 && t1 == t2 && c1 == c2,
-        _ => false, // Different source types are not equal
-    }
-}
-
-async fn install_extension(
-    config: &Config,
-    extension: &Extension,
-    ext_type: ExtensionType,
-) -> Result<String> {
-    let dest_dir = match ext_type {
-        ExtensionType::Plugin => config.plugins_dir().join(&extension.name),
-        ExtensionType::Theme => config.themes_dir().join(&extension.name),
-    };
-
-    // Create parent directories if they don't exist
-    if let Some(parent) = dest_dir.parent() {
-        fs::create_dir_all(parent)?;
-    }
-
-    let commit_hash = GitManager::clone_or_update(&extension.source, &dest_dir)?;
-
-    // For plugins, run bundle install and migrations if applicable
-    if matches!(ext_type, ExtensionType::Plugin) {
-        crate::commands::utils::run_plugin_setup(&dest_dir, config).await?;
-    }
-
-    Ok(commit_hash)
+    // Different source types are not equal, otherwise every field must match.
+    // The clone mode only decides how much history is fetched, not what is installed.
+    without_clone_mode(source1) == without_clone_mode(source2)
 }
 
-async fn uninstall_extension(config: &Config, extension: &LockedExtension) -> Result<()> {
-    let dest_dir = match extension.extension_type {
-        ExtensionType::Plugin => config.plugins_dir().join(&extension.name),
-        ExtensionType::Theme => config.themes_dir().join(&extension.name),
-    };
-
-    if dest_dir.exists() {
-        fs::remove_dir_all(&dest_dir)?;
+fn without_clone_mode(source: &Source) -> Source {
+    let mut source = source.clone();
+    if let Some(options) = source.git_options_mut() {
+        options.clone_mode = None;
     }
-
-    Ok(())
+    source
 }
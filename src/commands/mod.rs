@@ -1,18 +1,45 @@
 //! Command implementations for the rex CLI tool
 
+mod add;
+mod cache;
+mod check;
+mod dependencies;
+mod doctor;
 mod edit;
+mod envs;
+mod fetch;
+mod hooks;
+mod import;
 mod init;
 mod install;
+mod outdated;
+mod package;
+mod plan;
 mod reinstall;
+mod remove;
 mod state;
+mod status;
+mod switch;
+mod transaction;
 mod uninstall;
 mod update;
 mod utils;
 
+pub use add::add;
+pub use cache::{cache_clean, cache_list, cache_prune};
+pub use check::check;
+pub use doctor::doctor;
 pub use edit::edit;
+pub use envs::envs;
+pub use import::import;
 pub use init::init;
-pub use install::install;
+pub use install::{install, InstallOptions};
+pub use outdated::outdated;
+pub use package::package;
 pub use reinstall::reinstall;
+pub use remove::remove;
 pub use state::state;
+pub use status::status;
+pub use switch::switch;
 pub use uninstall::uninstall;
 pub use update::update;
@@ -0,0 +1,138 @@
+use crate::config::Config;
+use crate::error::{Result, RexerError};
+use crate::extension::{GitOptions, LockedExtension};
+use crate::git::{GitManager, RemoteRefs};
+use crate::version::VersionConstraint;
+use colored::*;
+
+pub async fn outdated() -> Result<()> {
+    let config = Config::new()?;
+
+    let lock_file = config
+        .load_lock_file()?
+        .ok_or_else(|| RexerError::LockFileError("No lock file found".to_string()))?;
+
+    let mut outdated_count = 0;
+    let mut failed_count = 0;
+
+    for ext in &lock_file.extensions {
+        let Some(options) = ext.source.git_options() else {
+            println!(" * {} (not a git source, skipped)", ext.name);
+            continue;
+        };
+
+        // An unreachable remote doesn't hide the state of the other extensions
+        let refs = match GitManager::ls_remote(&ext.source.full_url()) {
+            Ok(refs) => refs,
+            Err(e) => {
+                println!(
+                    " * {}: {} {}",
+                    ext.name.blue(),
+                    "failed:".red(),
+                    e.to_string().lines().next().unwrap_or_default()
+                );
+                failed_count += 1;
+                continue;
+            }
+        };
+        let status = OutdatedStatus::new(ext, options, &refs);
+        if status.is_outdated() {
+            outdated_count += 1;
+        }
+        status.print(&ext.name);
+    }
+
+    if outdated_count > 0 {
+        println!("{outdated_count} extension(s) outdated");
+    } else {
+        println!("All extensions are up to date");
+    }
+
+    if failed_count > 0 {
+        return Err(RexerError::GitError(format!(
+            "{failed_count} extension(s) could not be checked"
+        )));
+    }
+
+    Ok(())
+}
+
+/// How an installed extension compares with its remote repository
+struct OutdatedStatus<'a> {
+    /// Branch, tag or commit the extension follows
+    tracking: String,
+    current: Option<&'a str>,
+    /// Latest commit of the tracked branch, none for tags and commits
+    latest: Option<&'a str>,
+    pinned_tag: Option<&'a str>,
+    newest_tag: Option<&'a str>,
+}
+
+impl<'a> OutdatedStatus<'a> {
+    fn new(ext: &'a LockedExtension, options: &'a GitOptions, refs: &'a RemoteRefs) -> Self {
+        let (tracking, latest) = if let Some(commit) = &options.commit {
+            (format!("commit {}", short(commit)), None)
+        } else if let Some(tag) = &options.tag {
+            (format!("tag {tag}"), None)
+        } else if let Some(version) = &options.version {
+            // The newest tag within the constraint is what update would move to
+            let latest_tag = VersionConstraint::parse(version)
+                .ok()
+                .and_then(|constraint| {
+                    constraint.highest_match(refs.tags.iter().map(|(name, _)| name.as_str()))
+                })
+                .and_then(|tag| refs.tags.iter().find(|(name, _)| name == tag));
+            (
+                format!("version {version}"),
+                latest_tag.map(|(_, commit)| commit.as_str()),
+            )
+        } else if let Some(branch) = &options.branch {
+            (
+                format!("branch {branch}"),
+                refs.branches.get(branch).map(String::as_str),
+            )
+        } else {
+            ("default branch".to_string(), refs.head.as_deref())
+        };
+
+        Self {
+            tracking,
+            current: ext.commit_hash.as_deref(),
+            latest,
+            pinned_tag: options.tag.as_deref(),
+            newest_tag: refs.newest_tag(),
+        }
+    }
+
+    fn is_outdated(&self) -> bool {
+        let newer_commit = matches!((self.current, self.latest), (Some(current), Some(latest)) if current != latest);
+        let newer_tag = matches!((self.pinned_tag, self.newest_tag), (Some(pinned), Some(newest)) if pinned != newest);
+
+        newer_commit || newer_tag
+    }
+
+    fn print(&self, name: &str) {
+        let current = self.current.map(short).unwrap_or("unknown");
+
+        let mut line = match self.latest {
+            Some(latest) if self.current != Some(latest) => {
+                format!("{current} -> {}", short(latest).yellow())
+            }
+            _ => current.to_string(),
+        };
+        if let Some(newest_tag) = self.newest_tag {
+            let newest_tag = if self.pinned_tag.is_some_and(|pinned| pinned != newest_tag) {
+                newest_tag.yellow().to_string()
+            } else {
+                newest_tag.to_string()
+            };
+            line.push_str(&format!(", newest tag: {newest_tag}"));
+        }
+
+        println!(" * {} ({}): {line}", name.blue(), self.tracking);
+    }
+}
+
+fn short(commit: &str) -> &str {
+    &commit[..commit.len().min(8)]
+}
@@ -0,0 +1,178 @@
+use crate::archive::ArchiveManager;
+use crate::commands::fetch::{fetch_all, FetchJob};
+use crate::commands::install::verify_frozen;
+use crate::commands::utils::lock_entry;
+use crate::config::Config;
+use crate::error::{Result, RexerError};
+use crate::extension::{Extension, ExtensionType, LinkMode, LockedExtension, Source};
+use crate::local::LocalManager;
+use chrono::Utc;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the manifest at the root of a package
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Layout version of the packages written by this rex
+const PACKAGE_FORMAT: u32 = 1;
+
+/// Describes the extensions exported by `rex package`. The entries are those of the
+/// lock file, with the digest of the exported directory.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackageManifest {
+    pub format: u32,
+    pub rexer_version: String,
+    pub created_at: String,
+    pub env: String,
+    pub extensions: Vec<LockedExtension>,
+}
+
+impl PackageManifest {
+    /// Extract the package into the directory and read its manifest, checking that
+    /// every extension is there with the content the manifest records
+    pub fn extract(package: &Path, dir: &Path) -> Result<Self> {
+        ArchiveManager::extract_file(package, dir)?;
+
+        let manifest_path = dir.join(MANIFEST_FILE);
+        if !manifest_path.is_file() {
+            return Err(RexerError::PackageError(format!(
+                "{} has no {MANIFEST_FILE}, build it with rex package",
+                package.display()
+            )));
+        }
+        let manifest: Self = serde_json::from_str(&fs::read_to_string(manifest_path)?)?;
+
+        if manifest.format > PACKAGE_FORMAT {
+            return Err(RexerError::PackageError(format!(
+                "{} was built by rex {}, upgrade rex to install it",
+                package.display(),
+                manifest.rexer_version
+            )));
+        }
+
+        for locked_ext in &manifest.extensions {
+            let ext_dir = dir.join(package_path(locked_ext.extension_type, &locked_ext.name));
+            let digest = if ext_dir.is_dir() {
+                Some(LocalManager::digest(&ext_dir)?)
+            } else {
+                None
+            };
+            if digest.is_none() || digest != locked_ext.digest {
+                return Err(RexerError::PackageError(format!(
+                    "{} is missing or differs from the manifest of {}",
+                    locked_ext.name,
+                    package.display()
+                )));
+            }
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// Directory of an extension inside a package
+pub fn package_path(ext_type: ExtensionType, name: &str) -> PathBuf {
+    let type_dir = match ext_type {
+        ExtensionType::Plugin => "plugins",
+        ExtensionType::Theme => "themes",
+    };
+    Path::new(type_dir).join(name)
+}
+
+/// Export every extension of the lock file at its locked commit into a tarball that
+/// `rex install --from-package` installs without network access
+pub async fn package(output: PathBuf, jobs: usize) -> Result<()> {
+    let config = Config::new()?;
+    let lock_file = config.load_lock_file()?.ok_or_else(|| {
+        RexerError::LockFileError(
+            "rex package requires .extensions.lock, run rex install first".to_string(),
+        )
+    })?;
+
+    let work_dir = tempfile::Builder::new()
+        .prefix(".rexer-package")
+        .tempdir_in(&config.redmine_root)?;
+
+    let extensions: Vec<_> = lock_file
+        .extensions
+        .iter()
+        .map(|locked_ext| Extension {
+            name: locked_ext.name.clone(),
+            source: export_source(locked_ext),
+            hooks: locked_ext.hooks.clone(),
+        })
+        .collect();
+    let fetch_jobs = lock_file
+        .extensions
+        .iter()
+        .zip(&extensions)
+        .map(|(locked_ext, extension)| FetchJob {
+            source: extension.source.clone(),
+            dest_dir: work_dir
+                .path()
+                .join(package_path(locked_ext.extension_type, &locked_ext.name)),
+            message: format!("Exporting {}", locked_ext.name.blue()),
+            fallback: None,
+        })
+        .collect();
+    let fetched = fetch_all(&config, fetch_jobs, jobs).await?;
+
+    // Local paths may have changed since they were installed
+    let exported: Vec<_> = lock_file
+        .extensions
+        .iter()
+        .zip(&extensions)
+        .zip(fetched)
+        .map(|((locked_ext, extension), fetched)| {
+            lock_entry(extension, locked_ext.extension_type, fetched)
+        })
+        .collect();
+    verify_frozen(&lock_file, &exported)?;
+
+    let mut entries = vec![(
+        work_dir.path().join(MANIFEST_FILE),
+        PathBuf::from(MANIFEST_FILE),
+    )];
+    let mut manifest = PackageManifest {
+        format: PACKAGE_FORMAT,
+        rexer_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: Utc::now().to_rfc3339(),
+        env: lock_file.env.clone(),
+        extensions: Vec::new(),
+    };
+    for locked_ext in &lock_file.extensions {
+        let path = package_path(locked_ext.extension_type, &locked_ext.name);
+        let ext_dir = work_dir.path().join(&path);
+
+        let mut entry = locked_ext.clone();
+        entry.digest = Some(LocalManager::digest(&ext_dir)?);
+        manifest.extensions.push(entry);
+        entries.push((ext_dir, path));
+    }
+    fs::write(
+        work_dir.path().join(MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+
+    ArchiveManager::create(&output, &entries)?;
+
+    println!(
+        "Packaged {} extensions into {}",
+        manifest.extensions.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// The locked source, copying local paths so the package holds their content
+fn export_source(locked_ext: &LockedExtension) -> Source {
+    match locked_ext.pinned_source() {
+        Source::Path { path, .. } => Source::Path {
+            path,
+            link: LinkMode::Copy,
+        },
+        source => source,
+    }
+}
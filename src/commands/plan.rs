@@ -0,0 +1,255 @@
+use crate::commands::install::{InstallDiff, InstallOptions};
+use crate::commands::state::format_source_info;
+use crate::commands::utils::{has_gemfile, has_migrations};
+use crate::config::Config;
+use crate::error::Result;
+use crate::extension::{ExtensionType, LockFile, LockedExtension, Source};
+use crate::local::LocalManager;
+use colored::*;
+use serde::Serialize;
+
+/// What `rex install` would do, computed without touching the Redmine root
+#[derive(Debug, Serialize)]
+pub struct InstallPlan {
+    pub env: String,
+    pub previous_env: String,
+    pub add: Vec<PlannedChange>,
+    /// Locked extensions whose directory is missing
+    pub restore: Vec<PlannedChange>,
+    pub remove: Vec<PlannedChange>,
+    pub source_changed: Vec<PlannedChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlannedChange {
+    pub name: String,
+    pub extension_type: ExtensionType,
+    pub source: Source,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_source: Option<Source>,
+    /// Locked commit a restored extension is fetched at
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_hash: Option<String>,
+    /// Whether bundle install runs, None when it is only known after fetching the source
+    pub bundle_install: Option<bool>,
+    /// Whether plugin migrations run, None when it is only known after fetching the source
+    pub migrate: Option<bool>,
+    /// Whether the migrations of a removed plugin are rolled back
+    pub rollback_migrations: bool,
+}
+
+impl InstallPlan {
+    pub fn new(
+        config: &Config,
+        env: &str,
+        lock_file: &LockFile,
+        diff: &InstallDiff,
+        to_restore: &[&LockedExtension],
+        options: &InstallOptions,
+    ) -> Result<Self> {
+        let mut add = Vec::new();
+        for (extension, ext_type) in &diff.added {
+            let (bundle_install, migrate) = planned_setup(config, &extension.source, *ext_type)?;
+            add.push(PlannedChange {
+                name: extension.name.clone(),
+                extension_type: *ext_type,
+                source: extension.source.clone(),
+                previous_source: None,
+                commit_hash: None,
+                bundle_install,
+                migrate,
+                rollback_migrations: false,
+            });
+        }
+
+        let mut restore = Vec::new();
+        for locked_ext in to_restore {
+            let (bundle_install, migrate) =
+                planned_setup(config, &locked_ext.source, locked_ext.extension_type)?;
+            restore.push(PlannedChange {
+                name: locked_ext.name.clone(),
+                extension_type: locked_ext.extension_type,
+                source: locked_ext.source.clone(),
+                previous_source: None,
+                commit_hash: locked_ext.commit_hash.clone(),
+                bundle_install,
+                migrate,
+                rollback_migrations: false,
+            });
+        }
+
+        let mut source_changed = Vec::new();
+        for (extension, ext_type, old_locked) in &diff.source_changed {
+            let (bundle_install, migrate) = planned_setup(config, &extension.source, *ext_type)?;
+            source_changed.push(PlannedChange {
+                name: extension.name.clone(),
+                extension_type: *ext_type,
+                source: extension.source.clone(),
+                previous_source: Some(old_locked.source.clone()),
+                commit_hash: None,
+                bundle_install,
+                migrate,
+                rollback_migrations: false,
+            });
+        }
+
+        let mut remove = Vec::new();
+        for locked_ext in &diff.removed {
+            let dest_dir = config.extension_dir(locked_ext.extension_type, &locked_ext.name);
+            let rollback_migrations = !options.keep_data
+                && matches!(locked_ext.extension_type, ExtensionType::Plugin)
+                && has_migrations(&dest_dir)?;
+            remove.push(PlannedChange {
+                name: locked_ext.name.clone(),
+                extension_type: locked_ext.extension_type,
+                source: locked_ext.source.clone(),
+                previous_source: None,
+                commit_hash: None,
+                bundle_install: Some(false),
+                migrate: Some(false),
+                rollback_migrations,
+            });
+        }
+
+        Ok(Self {
+            env: env.to_string(),
+            previous_env: lock_file.env.clone(),
+            add,
+            restore,
+            remove,
+            source_changed,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.add.is_empty()
+            && self.restore.is_empty()
+            && self.remove.is_empty()
+            && self.source_changed.is_empty()
+    }
+
+    pub fn print(&self, json: bool) -> Result<()> {
+        if json {
+            println!("{}", serde_json::to_string_pretty(self)?);
+            return Ok(());
+        }
+
+        if self.env != self.previous_env {
+            println!("Switch environment: {} -> {}", self.previous_env, self.env);
+        }
+
+        if self.is_empty() {
+            println!("No changes for environment {}", self.env);
+            return Ok(());
+        }
+
+        for change in &self.add {
+            println!(
+                "{} {} ({}){}",
+                "+".green(),
+                change.name.blue(),
+                format_source_info(&change.source, &None),
+                format_setup(change)
+            );
+        }
+        for change in &self.restore {
+            let locked_commit = change
+                .commit_hash
+                .as_ref()
+                .map(|commit| format!(" at {}", &commit[..commit.len().min(8)]))
+                .unwrap_or_default();
+            println!(
+                "{} {} ({}) restore from lock{}{}",
+                "+".green(),
+                change.name.blue(),
+                format_source_info(&change.source, &None),
+                locked_commit,
+                format_setup(change)
+            );
+        }
+        for change in &self.source_changed {
+            let previous_source = change
+                .previous_source
+                .as_ref()
+                .map(|source| format_source_info(source, &None))
+                .unwrap_or_default();
+            println!(
+                "{} {} ({} -> {}){}",
+                "~".yellow(),
+                change.name.blue(),
+                previous_source,
+                format_source_info(&change.source, &None),
+                format_setup(change)
+            );
+        }
+        for change in &self.remove {
+            println!(
+                "{} {} ({}){}",
+                "-".red(),
+                change.name.blue(),
+                format_source_info(&change.source, &None),
+                format_setup(change)
+            );
+        }
+
+        let restore = if self.restore.is_empty() {
+            String::new()
+        } else {
+            format!(", {} to restore", self.restore.len())
+        };
+        println!(
+            "Plan: {} to add, {} to change, {} to remove{restore}",
+            self.add.len(),
+            self.source_changed.len(),
+            self.remove.len()
+        );
+        Ok(())
+    }
+}
+
+/// Predict the setup steps of an extension. Only local sources can be inspected
+/// before they are fetched.
+fn planned_setup(
+    config: &Config,
+    source: &Source,
+    ext_type: ExtensionType,
+) -> Result<(Option<bool>, Option<bool>)> {
+    if matches!(ext_type, ExtensionType::Theme) {
+        return Ok((Some(false), Some(false)));
+    }
+
+    match source {
+        Source::Path { path, .. } => {
+            let source_dir = LocalManager::resolve_path(&config.redmine_root, path)?;
+            Ok((
+                Some(has_gemfile(&source_dir)),
+                Some(has_migrations(&source_dir)?),
+            ))
+        }
+        _ => Ok((None, None)),
+    }
+}
+
+fn format_setup(change: &PlannedChange) -> String {
+    let mut steps = Vec::new();
+
+    match change.bundle_install {
+        Some(true) => steps.push("bundle install"),
+        Some(false) => {}
+        None => steps.push("bundle install if Gemfile exists"),
+    }
+    match change.migrate {
+        Some(true) => steps.push("migrations"),
+        Some(false) => {}
+        None => steps.push("migrations if db/migrate exists"),
+    }
+    if change.rollback_migrations {
+        steps.push("rollback migrations");
+    }
+
+    if steps.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", steps.join(", "))
+    }
+}
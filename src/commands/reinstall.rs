@@ -1,11 +1,12 @@
+use crate::commands::hooks::{HookEvent, HookRunner};
+use crate::commands::status::ensure_not_dirty;
+use crate::commands::utils::{install_extension, uninstall_extension};
 use crate::config::Config;
 use crate::error::{Result, RexerError};
-use crate::extension::{Extension, ExtensionType, LockedExtension};
-use chrono::Utc;
+use crate::extension::Extension;
 use colored::*;
-use std::fs;
 
-pub async fn reinstall(extension_name: String) -> Result<()> {
+pub async fn reinstall(extension_name: String, force: bool) -> Result<()> {
     let config = Config::new()?;
 
     let lock_file = config
@@ -18,69 +19,46 @@ pub async fn reinstall(extension_name: String) -> Result<()> {
         .find(|ext| ext.name == extension_name)
         .ok_or_else(|| RexerError::ExtensionNotFound(extension_name.clone()))?;
 
-    uninstall_extension(&config, extension).await?;
+    ensure_not_dirty(&config, extension, force)?;
+
+    let mut hooks = HookRunner::new(&config);
+
+    // The extension is installed again right away, so its data is kept
+    uninstall_extension(&config, extension, true).await?;
+    hooks.run(
+        &extension.name,
+        extension.hooks.as_ref(),
+        HookEvent::Uninstalled,
+    );
 
     let ext_type = extension.extension_type;
-    let commit_hash = install_extension(
+    let locked_ext = install_extension(
         &config,
         &Extension {
             name: extension.name.clone(),
             source: extension.source.clone(),
+            hooks: extension.hooks.clone(),
         },
         ext_type,
     )
     .await?;
+    hooks.run(
+        &extension.name,
+        extension.hooks.as_ref(),
+        HookEvent::Installed,
+    );
 
-    // Update lock file with new commit hash
+    // Update lock file with the reinstalled state
     let mut updated_lock = lock_file.clone();
-    if let Some(locked_ext) = updated_lock
+    if let Some(entry) = updated_lock
         .extensions
         .iter_mut()
         .find(|e| e.name == extension_name)
     {
-        locked_ext.commit_hash = Some(commit_hash);
-        locked_ext.installed_at = Utc::now().to_rfc3339();
+        *entry = locked_ext;
     }
     config.save_lock_file(&updated_lock)?;
 
     println!("Reinstalled {}", extension_name.blue());
-    Ok(())
-}
-
-async fn uninstall_extension(config: &Config, extension: &LockedExtension) -> Result<()> {
-    let dest_dir = match extension.extension_type {
-        ExtensionType::Plugin => config.plugins_dir().join(&extension.name),
-        ExtensionType::Theme => config.themes_dir().join(&extension.name),
-    };
-
-    if dest_dir.exists() {
-        fs::remove_dir_all(&dest_dir)?;
-    }
-
-    Ok(())
-}
-
-async fn install_extension(
-    config: &Config,
-    extension: &Extension,
-    ext_type: ExtensionType,
-) -> Result<String> {
-    let dest_dir = match ext_type {
-        ExtensionType::Plugin => config.plugins_dir().join(&extension.name),
-        ExtensionType::Theme => config.themes_dir().join(&extension.name),
-    };
-
-    // Create parent directories if they don't exist
-    if let Some(parent) = dest_dir.parent() {
-        fs::create_dir_all(parent)?;
-    }
-
-    let commit_hash = crate::git::GitManager::clone_or_update(&extension.source, &dest_dir)?;
-
-    // For plugins, run bundle install and migrations if applicable
-    if matches!(ext_type, ExtensionType::Plugin) {
-        crate::commands::utils::run_plugin_setup(&dest_dir, config).await?;
-    }
-
-    Ok(commit_hash)
+    hooks.finish()
 }
@@ -0,0 +1,40 @@
+use crate::commands::install::{install_selected, InstallOptions};
+use crate::commands::utils::ensure_default_env;
+use crate::config::Config;
+use crate::editor::ConfigEditor;
+use crate::error::{Result, RexerError};
+use colored::*;
+use std::fs;
+
+/// Delete an extension from .extensions.yml, keeping the rest of the file as it is
+pub async fn remove(name: String, install: bool, keep_data: bool) -> Result<()> {
+    let config = Config::new()?;
+    // Fails early when the configuration is missing or broken
+    config.load_extensions_config()?;
+    if install {
+        ensure_default_env(&config, "--install")?;
+    }
+
+    let mut editor = ConfigEditor::new(&fs::read_to_string(config.extensions_file_path())?);
+    if editor.remove(&name)?.is_none() {
+        return Err(RexerError::ExtensionNotFound(format!(
+            "{name} is not defined in the plugins or themes of .extensions.yml"
+        )));
+    }
+    config.save_extensions_file(&editor.content())?;
+
+    println!("Removed {} from .extensions.yml", name.blue());
+
+    if install {
+        install_selected(
+            &[name],
+            InstallOptions {
+                keep_data,
+                ..Default::default()
+            },
+        )
+        .await?;
+    }
+
+    Ok(())
+}
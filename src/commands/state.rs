@@ -1,6 +1,6 @@
 use crate::config::Config;
 use crate::error::Result;
-use crate::extension::{ExtensionType, Source};
+use crate::extension::{ExtensionType, GitOptions, LinkMode, LockedExtension, Source};
 
 pub async fn state() -> Result<()> {
     let config = Config::new()?;
@@ -25,16 +25,14 @@ pub async fn state() -> Result<()> {
             if !plugins.is_empty() {
                 println!("\nPlugins:");
                 for ext in &plugins {
-                    let source_info = format_source_info(&ext.source, &ext.commit_hash);
-                    println!(" * {} ({})", ext.name, source_info);
+                    println!(" * {} ({})", ext.name, format_locked_info(ext));
                 }
             }
 
             if !themes.is_empty() {
                 println!("\nThemes:");
                 for ext in &themes {
-                    let source_info = format_source_info(&ext.source, &ext.commit_hash);
-                    println!(" * {} ({})", ext.name, source_info);
+                    println!(" * {} ({})", ext.name, format_locked_info(ext));
                 }
             }
 
@@ -50,40 +48,30 @@ pub async fn state() -> Result<()> {
     Ok(())
 }
 
-fn format_source_info(source: &Source, commit_hash: &Option<String>) -> String {
+pub fn format_source_info(source: &Source, commit_hash: &Option<String>) -> String {
     let base_info = match source {
-        Source::Git {
-            url,
-            branch,
-            tag,
-            commit,
-        } => {
-            if let Some(commit) = commit {
-                format!("git: {url} at {commit}")
-            } else if let Some(tag) = tag {
-                format!("git: {url} at tag {tag}")
-            } else if let Some(branch) = branch {
-                format!("git: {url} at branch {branch}")
-            } else {
-                format!("git: {url}")
-            }
-        }
-        Source::GitHub {
+        Source::Git { url, options } => format_git_source("git", url, options),
+        Source::GitHub { repo, options } => format_git_source("github", repo, options),
+        Source::GitLab {
             repo,
-            branch,
-            tag,
-            commit,
-        } => {
-            if let Some(commit) = commit {
-                format!("github: {repo} at {commit}")
-            } else if let Some(tag) = tag {
-                format!("github: {repo} at tag {tag}")
-            } else if let Some(branch) = branch {
-                format!("github: {repo} at branch {branch}")
-            } else {
-                format!("github: {repo}")
-            }
-        }
+            host,
+            options,
+        } => format_git_source("gitlab", &hosted_location(host, repo), options),
+        Source::Bitbucket {
+            repo,
+            host,
+            options,
+        } => format_git_source("bitbucket", &hosted_location(host, repo), options),
+        Source::Gitea {
+            repo,
+            host,
+            options,
+        } => format_git_source("gitea", &hosted_location(host, repo), options),
+        Source::Path { path, link } => match link {
+            LinkMode::Symlink => format!("path: {path}"),
+            LinkMode::Copy => format!("path: {path} (copy)"),
+        },
+        Source::Archive { url, .. } => format!("archive: {url}"),
     };
 
     // If we have the actual installed commit, show it too
@@ -93,3 +81,43 @@ fn format_source_info(source: &Source, commit_hash: &Option<String>) -> String {
         base_info
     }
 }
+
+/// Source info of an installed extension, including the tag chosen for a version constraint
+fn format_locked_info(extension: &LockedExtension) -> String {
+    let source_info = format_source_info(&extension.source, &extension.commit_hash);
+
+    match &extension.resolved_tag {
+        Some(tag) => format!("{source_info}, tag: {tag}"),
+        None => source_info,
+    }
+}
+
+fn format_git_source(kind: &str, location: &str, options: &GitOptions) -> String {
+    let base_info = format_git_reference(kind, location, options);
+
+    match &options.path {
+        Some(path) => format!("{base_info}, path: {path}"),
+        None => base_info,
+    }
+}
+
+fn format_git_reference(kind: &str, location: &str, options: &GitOptions) -> String {
+    if let Some(commit) = &options.commit {
+        format!("{kind}: {location} at {commit}")
+    } else if let Some(tag) = &options.tag {
+        format!("{kind}: {location} at tag {tag}")
+    } else if let Some(branch) = &options.branch {
+        format!("{kind}: {location} at branch {branch}")
+    } else if let Some(version) = &options.version {
+        format!("{kind}: {location} at version {version}")
+    } else {
+        format!("{kind}: {location}")
+    }
+}
+
+fn hosted_location(host: &Option<String>, repo: &str) -> String {
+    match host {
+        Some(host) => format!("{}/{repo}", host.trim_end_matches('/')),
+        None => repo.to_string(),
+    }
+}
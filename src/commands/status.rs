@@ -0,0 +1,148 @@
+use crate::config::Config;
+use crate::error::{Result, RexerError};
+use crate::extension::{ExtensionType, LinkMode, LockedExtension, Source};
+use crate::git::GitManager;
+use crate::local::{LocalManager, BUNDLER_LOCK};
+use colored::*;
+use std::fs;
+
+pub async fn status() -> Result<()> {
+    let config = Config::new()?;
+
+    let lock_file = config
+        .load_lock_file()?
+        .ok_or_else(|| RexerError::LockFileError("No lock file found".to_string()))?;
+
+    let mut differing = 0;
+    for ext in &lock_file.extensions {
+        let tree = TreeStatus::check(&config, ext)?;
+        if !tree.is_clean() {
+            differing += 1;
+        }
+
+        let ext_type = match ext.extension_type {
+            ExtensionType::Plugin => "plugin",
+            ExtensionType::Theme => "theme",
+        };
+        println!(
+            " * {} [{ext_type}]: {}",
+            ext.name.blue(),
+            tree.describe(ext)
+        );
+    }
+
+    if differing > 0 {
+        return Err(RexerError::LocalModifications(format!(
+            "{differing} extension(s) differ from .extensions.lock"
+        )));
+    }
+
+    println!("All extensions match .extensions.lock");
+    Ok(())
+}
+
+/// How an installed extension directory differs from its lock entry
+#[derive(Debug, Default)]
+pub struct TreeStatus {
+    pub missing: bool,
+    /// Checked out commit when it is not the locked one
+    pub other_commit: Option<String>,
+    /// Changed and untracked files in a Git working tree
+    pub modified: Vec<String>,
+    /// Files changed in an extension installed without Git metadata
+    pub content_changed: bool,
+}
+
+impl TreeStatus {
+    pub fn check(config: &Config, extension: &LockedExtension) -> Result<Self> {
+        let dest_dir = config.extension_dir(extension.extension_type, &extension.name);
+        if fs::symlink_metadata(&dest_dir).is_err() {
+            return Ok(Self {
+                missing: true,
+                ..Default::default()
+            });
+        }
+
+        let mut status = Self::default();
+
+        // Only Git sources are checked with Git. A symlinked path source may point at a
+        // developer's own repository, whose checkout is not ours to judge.
+        if extension.source.git_options().is_some() && dest_dir.join(".git").exists() {
+            let head = GitManager::get_current_commit_hash(&dest_dir)?;
+            if extension.commit_hash.as_ref() != Some(&head) {
+                status.other_commit = Some(head);
+            }
+            status.modified = GitManager::modified_files(&dest_dir)?
+                .into_iter()
+                .filter(|path| path != BUNDLER_LOCK)
+                .collect();
+        } else if let Some(digest) = &extension.digest {
+            // A symlinked path is the developer's own working copy, changes are expected
+            let symlinked = matches!(
+                extension.source,
+                Source::Path {
+                    link: LinkMode::Symlink,
+                    ..
+                }
+            );
+            status.content_changed = !symlinked && LocalManager::digest(&dest_dir)? != *digest;
+        }
+
+        Ok(status)
+    }
+
+    pub fn is_clean(&self) -> bool {
+        !self.missing && self.other_commit.is_none() && !self.is_dirty()
+    }
+
+    /// Whether fetching over the directory would discard local changes
+    pub fn is_dirty(&self) -> bool {
+        !self.modified.is_empty() || self.content_changed
+    }
+
+    fn describe(&self, extension: &LockedExtension) -> String {
+        if self.missing {
+            return "missing".red().to_string();
+        }
+
+        let mut problems = Vec::new();
+        if let Some(other_commit) = &self.other_commit {
+            let locked = extension.commit_hash.as_deref().unwrap_or("none");
+            problems.push(format!(
+                "at {}, locked {}",
+                &other_commit[..8],
+                &locked[..locked.len().min(8)]
+            ));
+        }
+        if !self.modified.is_empty() {
+            problems.push(format!("modified: {}", self.modified.join(", ")));
+        }
+        if self.content_changed {
+            problems.push("content differs from .extensions.lock".to_string());
+        }
+
+        if problems.is_empty() {
+            "ok".green().to_string()
+        } else {
+            problems.join("; ").yellow().to_string()
+        }
+    }
+}
+
+/// Refuse to overwrite an extension with local changes unless forced
+pub fn ensure_not_dirty(config: &Config, extension: &LockedExtension, force: bool) -> Result<()> {
+    let tree = TreeStatus::check(config, extension)?;
+    if !tree.is_dirty() || force {
+        return Ok(());
+    }
+
+    let details = if tree.modified.is_empty() {
+        "files changed since it was installed".to_string()
+    } else {
+        tree.modified.join(", ")
+    };
+    Err(RexerError::LocalModifications(format!(
+        "{} has local changes ({details}), use --force to overwrite them",
+        extension.name
+    )))
+}
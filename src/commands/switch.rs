@@ -0,0 +1,22 @@
+use crate::commands::install::{install_env, InstallOptions};
+use crate::config::Config;
+use crate::error::Result;
+use colored::*;
+
+pub async fn switch(env: String, options: InstallOptions) -> Result<()> {
+    let config = Config::new()?;
+    let extensions_config = config.load_extensions_config()?;
+    let current_lock = config.load_lock_file()?;
+
+    install_env(
+        &config,
+        &extensions_config,
+        &env,
+        current_lock.as_ref(),
+        &options,
+    )
+    .await?;
+
+    println!("Switched to {}", env.blue());
+    Ok(())
+}
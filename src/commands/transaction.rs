@@ -0,0 +1,135 @@
+use crate::config::Config;
+use crate::error::Result;
+use crate::extension::ExtensionType;
+use crate::local::LocalManager;
+use log::debug;
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+/// Swaps extension directories into the Redmine root and restores the previous
+/// directories and lock file if anything fails before the transaction is committed.
+///
+/// Extensions are first fetched into a staging directory next to `plugins/`, so a
+/// failed clone never touches the installed extensions. Database migrations that
+/// already ran cannot be reverted.
+pub struct Transaction<'a> {
+    config: &'a Config,
+    work_dir: TempDir,
+    lock_backup: Option<Vec<u8>>,
+    moves: Vec<Move>,
+}
+
+/// A directory placed into or taken out of the Redmine root
+struct Move {
+    dest: PathBuf,
+    backup: Option<PathBuf>,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn begin(config: &'a Config) -> Result<Self> {
+        let work_dir = tempfile::Builder::new()
+            .prefix(".rexer-staging")
+            .tempdir_in(&config.redmine_root)?;
+
+        let lock_path = config.lock_file_path();
+        let lock_backup = if lock_path.exists() {
+            Some(fs::read(&lock_path)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            config,
+            work_dir,
+            lock_backup,
+            moves: Vec::new(),
+        })
+    }
+
+    /// Directory to fetch an extension into before it is installed
+    pub fn staging_dir(&self, ext_type: ExtensionType, name: &str) -> PathBuf {
+        self.work_path("staged", ext_type, name)
+    }
+
+    /// Move a staged extension into place, keeping the current directory as a backup
+    pub fn install(&mut self, ext_type: ExtensionType, name: &str) -> Result<PathBuf> {
+        let dest = self.config.extension_dir(ext_type, name);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let backup = self.backup(ext_type, name)?;
+        self.moves.push(Move {
+            dest: dest.clone(),
+            backup,
+        });
+
+        fs::rename(self.staging_dir(ext_type, name), &dest)?;
+        Ok(dest)
+    }
+
+    /// Move an installed extension out of place, keeping it as a backup
+    pub fn remove(&mut self, ext_type: ExtensionType, name: &str) -> Result<()> {
+        let dest = self.config.extension_dir(ext_type, name);
+
+        if let Some(backup) = self.backup(ext_type, name)? {
+            self.moves.push(Move {
+                dest,
+                backup: Some(backup),
+            });
+        }
+        Ok(())
+    }
+
+    /// Keep the changes and delete the backups
+    pub fn commit(self) {
+        debug!(
+            "Committing changes, removing {}",
+            self.work_dir.path().display()
+        );
+    }
+
+    /// Restore the directories and lock file as they were when the transaction began
+    pub fn rollback(mut self) -> Result<()> {
+        while let Some(applied) = self.moves.pop() {
+            debug!("Restoring {}", applied.dest.display());
+            LocalManager::remove(&applied.dest)?;
+            if let Some(backup) = applied.backup {
+                fs::rename(backup, &applied.dest)?;
+            }
+        }
+
+        let lock_path = self.config.lock_file_path();
+        match &self.lock_backup {
+            Some(content) => fs::write(&lock_path, content)?,
+            None if lock_path.exists() => fs::remove_file(&lock_path)?,
+            None => {}
+        }
+
+        Ok(())
+    }
+
+    fn backup(&self, ext_type: ExtensionType, name: &str) -> Result<Option<PathBuf>> {
+        let dest = self.config.extension_dir(ext_type, name);
+        if fs::symlink_metadata(&dest).is_err() {
+            return Ok(None);
+        }
+
+        let backup = self.work_path("backup", ext_type, name);
+        if let Some(parent) = backup.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&dest, &backup)?;
+
+        Ok(Some(backup))
+    }
+
+    fn work_path(&self, kind: &str, ext_type: ExtensionType, name: &str) -> PathBuf {
+        let type_dir = match ext_type {
+            ExtensionType::Plugin => "plugins",
+            ExtensionType::Theme => "themes",
+        };
+        self.work_dir.path().join(kind).join(type_dir).join(name)
+    }
+}
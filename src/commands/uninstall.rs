@@ -1,40 +1,40 @@
+use crate::commands::hooks::{HookEvent, HookRunner};
+use crate::commands::utils::uninstall_extension;
 use crate::config::Config;
 use crate::error::{Result, RexerError};
-use crate::extension::{LockFile, LockedExtension};
+use crate::extension::LockFile;
 use colored::*;
-use std::fs;
 
-pub async fn uninstall() -> Result<()> {
+pub async fn uninstall(keep_data: bool) -> Result<()> {
     let config = Config::new()?;
 
     let lock_file = config
         .load_lock_file()?
         .ok_or_else(|| RexerError::LockFileError("No lock file found".to_string()))?;
 
-    uninstall_all_extensions(&config, &lock_file).await?;
+    let mut hooks = HookRunner::new(&config);
+
+    uninstall_all_extensions(&config, &lock_file, keep_data, &mut hooks).await?;
     config.delete_lock_file()?;
 
     println!("Uninstalled all extensions");
-    Ok(())
+    hooks.finish()
 }
 
-async fn uninstall_all_extensions(config: &Config, lock_file: &LockFile) -> Result<()> {
+async fn uninstall_all_extensions(
+    config: &Config,
+    lock_file: &LockFile,
+    keep_data: bool,
+    hooks: &mut HookRunner<'_>,
+) -> Result<()> {
     for extension in &lock_file.extensions {
         println!("Uninstalling {}...", extension.name.blue());
-        uninstall_extension(config, extension).await?;
-    }
-    Ok(())
-}
-
-async fn uninstall_extension(config: &Config, extension: &LockedExtension) -> Result<()> {
-    let dest_dir = match extension.extension_type {
-        crate::extension::ExtensionType::Plugin => config.plugins_dir().join(&extension.name),
-        crate::extension::ExtensionType::Theme => config.themes_dir().join(&extension.name),
-    };
-
-    if dest_dir.exists() {
-        fs::remove_dir_all(&dest_dir)?;
+        uninstall_extension(config, extension, keep_data).await?;
+        hooks.run(
+            &extension.name,
+            extension.hooks.as_ref(),
+            HookEvent::Uninstalled,
+        );
     }
-
     Ok(())
 }
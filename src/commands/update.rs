@@ -1,18 +1,22 @@
+use crate::commands::fetch::{fetch_all, FetchJob};
+use crate::commands::hooks::{HookEvent, HookRunner};
+use crate::commands::status::ensure_not_dirty;
+use crate::commands::utils::{run_plugin_setup, FetchedSource};
 use crate::config::Config;
 use crate::error::{Result, RexerError};
-use crate::extension::LockedExtension;
+use crate::extension::{ExtensionType, LockedExtension, Source};
 use crate::git::GitManager;
 use chrono::Utc;
 use colored::*;
 
-pub async fn update(extension_names: Vec<String>) -> Result<()> {
+pub async fn update(extension_names: Vec<String>, jobs: usize, force: bool) -> Result<()> {
     let config = Config::new()?;
 
     let lock_file = config
         .load_lock_file()?
         .ok_or_else(|| RexerError::LockFileError("No lock file found".to_string()))?;
 
-    let extensions_to_update = if extension_names.is_empty() {
+    let extensions_to_update: Vec<LockedExtension> = if extension_names.is_empty() {
         lock_file.extensions.clone()
     } else {
         lock_file
@@ -28,24 +32,85 @@ pub async fn update(extension_names: Vec<String>) -> Result<()> {
         return Ok(());
     }
 
+    for ext in &extensions_to_update {
+        let dest_dir = config.extension_dir(ext.extension_type, &ext.name);
+        if !dest_dir.exists() {
+            return Err(RexerError::ExtensionNotFound(format!(
+                "Extension directory not found: {}",
+                dest_dir.display()
+            )));
+        }
+        ensure_not_dirty(&config, ext, force)?;
+    }
+
+    // Forced updates throw away local changes so the checkout can move on
+    if force {
+        for ext in &extensions_to_update {
+            let dest_dir = config.extension_dir(ext.extension_type, &ext.name);
+            if ext.source.git_options().is_some() && dest_dir.join(".git").exists() {
+                GitManager::discard_changes(&dest_dir)?;
+            }
+        }
+    }
+
+    // Archives are pinned by their checksum, so there is nothing newer to fetch.
+    // Git sources are fetched in place, local path sources are re-synced.
+    let fetch_jobs = extensions_to_update
+        .iter()
+        .filter(|ext| !is_archive(ext))
+        .map(|ext| FetchJob {
+            source: ext.source.clone(),
+            dest_dir: config.extension_dir(ext.extension_type, &ext.name),
+            message: format!("Updating {}", ext.name.blue()),
+            fallback: None,
+        })
+        .collect();
+    let mut fetched = fetch_all(&config, fetch_jobs, jobs).await?.into_iter();
+
     // Track updates for lock file
     let mut updated_lock = lock_file.clone();
     let mut any_updated = false;
+    let mut hooks = HookRunner::new(&config);
 
     for ext in &extensions_to_update {
-        println!("Updating {}...", ext.name.blue());
-        let new_commit_hash = update_extension_and_get_hash(&config, ext).await?;
+        let fetched = if is_archive(ext) {
+            FetchedSource {
+                commit_hash: ext.commit_hash.clone(),
+                resolved_path: ext.resolved_path.clone(),
+                digest: ext.digest.clone(),
+                tag: ext.resolved_tag.clone(),
+                submodule_commits: ext.submodule_commits.clone(),
+            }
+        } else {
+            fetched
+                .next()
+                .expect("every fetched extension has a result")
+        };
 
-        // Update the lock file entry if commit hash changed
+        // Bundle install and migrations run one extension at a time
+        if !is_archive(ext) && matches!(ext.extension_type, ExtensionType::Plugin) {
+            let dest_dir = config.extension_dir(ext.extension_type, &ext.name);
+            run_plugin_setup(&dest_dir, &config).await?;
+        }
+
+        // Update the lock file entry if commit hash or content digest changed
         if let Some(locked_ext) = updated_lock
             .extensions
             .iter_mut()
             .find(|e| e.name == ext.name)
         {
-            if locked_ext.commit_hash.as_ref() != Some(&new_commit_hash) {
-                locked_ext.commit_hash = Some(new_commit_hash);
+            if locked_ext.commit_hash != fetched.commit_hash
+                || locked_ext.digest != fetched.digest
+                || locked_ext.submodule_commits != fetched.submodule_commits
+            {
+                locked_ext.commit_hash = fetched.commit_hash;
+                locked_ext.resolved_path = fetched.resolved_path;
+                locked_ext.digest = fetched.digest;
+                locked_ext.resolved_tag = fetched.tag;
+                locked_ext.submodule_commits = fetched.submodule_commits;
                 locked_ext.installed_at = Utc::now().to_rfc3339();
                 any_updated = true;
+                hooks.run(&ext.name, ext.hooks.as_ref(), HookEvent::Updated);
             }
         }
     }
@@ -58,33 +123,9 @@ pub async fn update(extension_names: Vec<String>) -> Result<()> {
         println!("All extensions are already up to date");
     }
 
-    Ok(())
+    hooks.finish()
 }
 
-async fn update_extension_and_get_hash(
-    config: &Config,
-    extension: &LockedExtension,
-) -> Result<String> {
-    let dest_dir = match extension.extension_type {
-        crate::extension::ExtensionType::Plugin => config.plugins_dir().join(&extension.name),
-        crate::extension::ExtensionType::Theme => config.themes_dir().join(&extension.name),
-    };
-
-    if dest_dir.exists() {
-        let commit_hash = GitManager::clone_or_update(&extension.source, &dest_dir)?;
-
-        if matches!(
-            extension.extension_type,
-            crate::extension::ExtensionType::Plugin
-        ) {
-            crate::commands::utils::run_plugin_setup(&dest_dir, config).await?;
-        }
-
-        Ok(commit_hash)
-    } else {
-        Err(RexerError::ExtensionNotFound(format!(
-            "Extension directory not found: {}",
-            dest_dir.display()
-        )))
-    }
+fn is_archive(extension: &LockedExtension) -> bool {
+    matches!(extension.source, Source::Archive { .. })
 }
@@ -1,24 +1,329 @@
+use crate::archive::ArchiveManager;
 use crate::config::Config;
 use crate::error::{Result, RexerError};
+use crate::extension::{
+    Extension, ExtensionType, LinkMode, LockedExtension, Source, Submodules, DEFAULT_ENV,
+};
+use crate::git::GitManager;
+use crate::local::LocalManager;
+use crate::version::VersionConstraint;
 use anyhow::Context;
-use log::info;
-use std::path::Path;
+use chrono::Utc;
+use log::{debug, info};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path};
 use std::process::Command;
 
-pub async fn run_plugin_setup(plugin_dir: &Path, config: &Config) -> Result<()> {
-    let gemfile_path = plugin_dir.join("Gemfile");
+/// State of an extension source after it has been fetched into the Redmine root
+pub struct FetchedSource {
+    pub commit_hash: Option<String>,
+    pub resolved_path: Option<String>,
+    pub digest: Option<String>,
+    /// Tag chosen for a version constraint
+    pub tag: Option<String>,
+    /// Commits of the checked out submodules, by path
+    pub submodule_commits: BTreeMap<String, String>,
+}
+
+pub async fn fetch_source(
+    config: &Config,
+    source: &Source,
+    dest_dir: &Path,
+) -> Result<FetchedSource> {
+    // Create parent directories if they don't exist
+    if let Some(parent) = dest_dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    match source {
+        Source::Path { path, link } => {
+            let resolved_path = LocalManager::resolve_path(&config.redmine_root, path)?;
+            let digest = LocalManager::sync(&resolved_path, *link, dest_dir)?;
+            Ok(FetchedSource {
+                commit_hash: None,
+                resolved_path: Some(resolved_path.display().to_string()),
+                digest: Some(digest),
+                tag: None,
+                submodule_commits: BTreeMap::new(),
+            })
+        }
+        Source::Archive {
+            url,
+            sha256,
+            strip_components,
+        } => {
+            ArchiveManager::install(url, sha256, strip_components.unwrap_or(0), dest_dir).await?;
+            // The archive checksum stays in the source, the lock records the extracted files
+            let digest = LocalManager::digest(dest_dir)?;
+            Ok(FetchedSource {
+                commit_hash: None,
+                resolved_path: None,
+                digest: Some(digest),
+                tag: None,
+                submodule_commits: BTreeMap::new(),
+            })
+        }
+        _ => {
+            let repositories_dir = config.repositories_dir();
+            let source = source.clone();
+            let dest_dir = dest_dir.to_path_buf();
+            let offline = config.offline;
+
+            // Git commands block, so keep them off the async runtime threads
+            tokio::task::spawn_blocking(move || {
+                fetch_git(&repositories_dir, &source, &dest_dir, offline)
+            })
+            .await
+            .map_err(anyhow::Error::from)?
+        }
+    }
+}
+
+fn fetch_git(
+    repositories_dir: &Path,
+    source: &Source,
+    dest_dir: &Path,
+    offline: bool,
+) -> Result<FetchedSource> {
+    let (source, tag) = resolve_version(source)?;
+
+    let fetched = match source
+        .git_options()
+        .and_then(|options| options.path.as_deref())
+    {
+        Some(subdirectory) => {
+            fetch_subdirectory(repositories_dir, &source, subdirectory, dest_dir, offline)?
+        }
+        None => FetchedSource {
+            commit_hash: Some(GitManager::clone_or_update(&source, dest_dir, offline)?),
+            resolved_path: None,
+            digest: None,
+            tag: None,
+            submodule_commits: GitManager::submodule_commits(dest_dir, submodules(&source))?,
+        },
+    };
+
+    Ok(FetchedSource { tag, ..fetched })
+}
+
+/// Pin a source with a version constraint to the highest matching remote tag.
+/// Returns the source to check out and the chosen tag.
+fn resolve_version(source: &Source) -> Result<(Source, Option<String>)> {
+    let Some(options) = source.git_options() else {
+        return Ok((source.clone(), None));
+    };
+    // Source::validate already rejected a version combined with another reference
+    let Some(version) = &options.version else {
+        return Ok((source.clone(), None));
+    };
+
+    let constraint = VersionConstraint::parse(version)?;
+    let refs = GitManager::ls_remote(&source.full_url())?;
+    let tag = constraint
+        .highest_match(refs.tags.iter().map(|(name, _)| name.as_str()))
+        .ok_or_else(|| {
+            RexerError::GitError(format!(
+                "No tag of {} matches version {version}",
+                source.full_url()
+            ))
+        })?
+        .to_string();
+    debug!(
+        "Resolved version {version} of {} to {tag}",
+        source.full_url()
+    );
+
+    let mut pinned = source.clone();
+    if let Some(options) = pinned.git_options_mut() {
+        options.tag = Some(tag.clone());
+    }
+
+    Ok((pinned, Some(tag)))
+}
+
+/// Clone the repository once into the shared repositories directory and copy
+/// only the requested subdirectory to the destination
+fn fetch_subdirectory(
+    repositories_dir: &Path,
+    source: &Source,
+    subdirectory: &str,
+    dest_dir: &Path,
+    offline: bool,
+) -> Result<FetchedSource> {
+    if !Path::new(subdirectory)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(RexerError::InvalidConfig(format!(
+            "Subdirectory must be a relative path inside the repository: {subdirectory}"
+        )));
+    }
+
+    let repo_dir = repositories_dir.join(GitManager::repository_dir_name(&source.full_url()));
+    if let Some(parent) = repo_dir.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let commit_hash = GitManager::clone_or_update(source, &repo_dir, offline)?;
+
+    let source_dir = repo_dir.join(subdirectory);
+    if !source_dir.is_dir() {
+        return Err(RexerError::InvalidConfig(format!(
+            "Subdirectory {subdirectory} not found in {}",
+            source.full_url()
+        )));
+    }
+
+    let digest = LocalManager::sync(&source_dir, LinkMode::Copy, dest_dir)?;
+
+    // Only the submodules inside the subdirectory end up in the extension
+    let submodule_commits = GitManager::submodule_commits(&repo_dir, submodules(source))?
+        .into_iter()
+        .filter_map(|(path, commit)| {
+            let path = Path::new(&path).strip_prefix(subdirectory).ok()?;
+            Some((path.to_string_lossy().to_string(), commit))
+        })
+        .collect();
+
+    Ok(FetchedSource {
+        commit_hash: Some(commit_hash),
+        resolved_path: None,
+        digest: Some(digest),
+        tag: None,
+        submodule_commits,
+    })
+}
+
+fn submodules(source: &Source) -> Submodules {
+    source
+        .git_options()
+        .map(|options| options.submodules)
+        .unwrap_or_default()
+}
+
+pub async fn install_extension(
+    config: &Config,
+    extension: &Extension,
+    ext_type: ExtensionType,
+) -> Result<LockedExtension> {
+    let dest_dir = config.extension_dir(ext_type, &extension.name);
+
+    let locked_ext = fetch_extension(config, extension, ext_type, &dest_dir).await?;
 
-    if gemfile_path.exists() {
+    // For plugins, run bundle install and migrations if applicable
+    if matches!(ext_type, ExtensionType::Plugin) {
+        run_plugin_setup(&dest_dir, config).await?;
+    }
+
+    Ok(locked_ext)
+}
+
+/// Fetch the extension source into the given directory and build its lock entry
+pub async fn fetch_extension(
+    config: &Config,
+    extension: &Extension,
+    ext_type: ExtensionType,
+    dest_dir: &Path,
+) -> Result<LockedExtension> {
+    let fetched = fetch_source(config, &extension.source, dest_dir).await?;
+    Ok(lock_entry(extension, ext_type, fetched))
+}
+
+pub fn lock_entry(
+    extension: &Extension,
+    ext_type: ExtensionType,
+    fetched: FetchedSource,
+) -> LockedExtension {
+    LockedExtension {
+        name: extension.name.clone(),
+        extension_type: ext_type,
+        source: extension.source.clone(),
+        commit_hash: fetched.commit_hash,
+        installed_at: Utc::now().to_rfc3339(),
+        hooks: extension.hooks.clone(),
+        resolved_path: fetched.resolved_path,
+        digest: fetched.digest,
+        resolved_tag: fetched.tag,
+        submodule_commits: fetched.submodule_commits,
+    }
+}
+
+/// Fail unless the default environment is installed. `action` edits the top-level
+/// lists, which other environments don't use.
+pub fn ensure_default_env(config: &Config, action: &str) -> Result<()> {
+    match config.load_lock_file()? {
+        Some(lock_file) if lock_file.env != DEFAULT_ENV => Err(RexerError::InvalidConfig(format!(
+            "{action} only applies to the default environment, but {} is active",
+            lock_file.env
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the installed directory still has the commit or content recorded in its lock entry
+pub fn matches_lock(config: &Config, extension: &LockedExtension) -> Result<bool> {
+    let dest_dir = config.extension_dir(extension.extension_type, &extension.name);
+    if fs::symlink_metadata(&dest_dir).is_err() {
+        return Ok(false);
+    }
+
+    if let Some(digest) = &extension.digest {
+        return Ok(LocalManager::digest(&dest_dir)? == *digest);
+    }
+
+    match &extension.commit_hash {
+        Some(commit_hash) => Ok(GitManager::get_current_commit_hash(&dest_dir)
+            .is_ok_and(|current| current == *commit_hash)),
+        None => Ok(true),
+    }
+}
+
+/// Remove an installed extension. Unless `keep_data` is set, the migrations of
+/// plugins are rolled back first so their tables are dropped as well.
+pub async fn uninstall_extension(
+    config: &Config,
+    extension: &LockedExtension,
+    keep_data: bool,
+) -> Result<()> {
+    let dest_dir = config.extension_dir(extension.extension_type, &extension.name);
+
+    if !keep_data && matches!(extension.extension_type, ExtensionType::Plugin) {
+        rollback_plugin_migrations(&dest_dir, config).await?;
+    }
+
+    // Local path sources may be installed as symlinks
+    LocalManager::remove(&dest_dir)?;
+
+    Ok(())
+}
+
+pub fn has_gemfile(plugin_dir: &Path) -> bool {
+    plugin_dir.join("Gemfile").exists()
+}
+
+pub fn has_migrations(plugin_dir: &Path) -> Result<bool> {
+    let migrations_dir = plugin_dir.join("db").join("migrate");
+    Ok(migrations_dir.exists() && migrations_dir.read_dir()?.next().is_some())
+}
+
+pub async fn run_plugin_setup(plugin_dir: &Path, config: &Config) -> Result<()> {
+    if has_gemfile(plugin_dir) {
         info!(
             "Running bundle install for plugin at {}",
             plugin_dir.display()
         );
-        run_command("bundle", &["install"], Some(plugin_dir), config)?;
+        // Offline, bundler only uses gems that are already installed or vendored
+        let args: &[&str] = if config.offline {
+            &["install", "--local"]
+        } else {
+            &["install"]
+        };
+        run_command("bundle", args, Some(plugin_dir), config)?;
     }
 
     // Check for migrations
-    let migrations_dir = plugin_dir.join("db").join("migrate");
-    if migrations_dir.exists() && migrations_dir.read_dir()?.next().is_some() {
+    if has_migrations(plugin_dir)? {
         info!("Running migrations for plugin at {}", plugin_dir.display());
         let plugin_name = plugin_dir
             .file_name()
@@ -41,6 +346,34 @@ pub async fn run_plugin_setup(plugin_dir: &Path, config: &Config) -> Result<()>
     Ok(())
 }
 
+pub async fn rollback_plugin_migrations(plugin_dir: &Path, config: &Config) -> Result<()> {
+    if !has_migrations(plugin_dir)? {
+        return Ok(());
+    }
+
+    info!(
+        "Rolling back migrations for plugin at {}",
+        plugin_dir.display()
+    );
+    let plugin_name = plugin_dir
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown");
+
+    run_command(
+        "bundle",
+        &[
+            "exec",
+            "rake",
+            "redmine:plugins:migrate",
+            &format!("NAME={plugin_name}"),
+            "VERSION=0",
+        ],
+        Some(&config.redmine_root),
+        config,
+    )
+}
+
 pub fn run_command(
     command: &str,
     args: &[&str],
@@ -1,14 +1,20 @@
 use crate::error::{Result, RexerError};
-use crate::extension::{ExtensionsConfig, LockFile};
+use crate::extension::{ExtensionType, ExtensionsConfig, LockFile};
+use crate::validation;
 use std::fs;
 use std::path::PathBuf;
 
 pub const EXTENSIONS_FILE: &str = ".extensions.yml";
 pub const LOCK_FILE: &str = ".extensions.lock";
+pub const DEFAULT_JOBS: usize = 4;
 
+#[derive(Clone)]
 pub struct Config {
     pub command_prefix: Option<String>,
     pub redmine_root: PathBuf,
+    /// Never contact a remote: Git only reads from the mirror cache and bundler
+    /// only uses installed gems
+    pub offline: bool,
 }
 
 impl Config {
@@ -19,6 +25,7 @@ impl Config {
         Ok(Self {
             command_prefix,
             redmine_root,
+            offline: false,
         })
     }
 
@@ -38,6 +45,18 @@ impl Config {
         self.redmine_root.join("themes")
     }
 
+    /// Shared clones of repositories containing several extensions
+    pub fn repositories_dir(&self) -> PathBuf {
+        self.redmine_root.join(".rexer").join("repos")
+    }
+
+    pub fn extension_dir(&self, ext_type: ExtensionType, name: &str) -> PathBuf {
+        match ext_type {
+            ExtensionType::Plugin => self.plugins_dir().join(name),
+            ExtensionType::Theme => self.themes_dir().join(name),
+        }
+    }
+
     pub fn load_extensions_config(&self) -> Result<ExtensionsConfig> {
         let path = self.extensions_file_path();
         if !path.exists() {
@@ -45,15 +64,13 @@ impl Config {
         }
 
         let content = fs::read_to_string(&path)?;
-        let config: ExtensionsConfig = serde_yaml::from_str(&content)?;
-        Ok(config)
+        validation::parse_extensions_config(EXTENSIONS_FILE, &content)
     }
 
-    #[allow(dead_code)]
-    pub fn save_extensions_config(&self, config: &ExtensionsConfig) -> Result<()> {
-        let path = self.extensions_file_path();
-        let content = serde_yaml::to_string(config)?;
-        fs::write(&path, content)?;
+    /// Write edited configuration content, refusing content that doesn't pass validation
+    pub fn save_extensions_file(&self, content: &str) -> Result<()> {
+        validation::parse_extensions_config(EXTENSIONS_FILE, content)?;
+        fs::write(self.extensions_file_path(), content)?;
         Ok(())
     }
 
@@ -101,6 +118,15 @@ themes:
   #   git:
   #     url: "https://github.com/user/my_theme.git"
   #     branch: "main"
+
+# Extensions for other environments, activated with `rex switch <env>`
+# environments:
+#   staging:
+#     plugins:
+#       - name: redmine_issues_panel
+#         github:
+#           repo: "redmica/redmine_issues_panel"
+#           branch: "main"
 "#;
 
         let path = self.extensions_file_path();
@@ -0,0 +1,173 @@
+use crate::error::{Result, RexerError};
+use crate::extension::{Extension, ExtensionType};
+
+/// Edits the top-level plugin and theme lists of .extensions.yml as text, so the
+/// comments, ordering and formatting of the rest of the file are kept
+pub struct ConfigEditor {
+    lines: Vec<String>,
+}
+
+/// Lines of a top-level list, from its key to its last indented line
+struct Block {
+    key_line: usize,
+    end: usize,
+}
+
+impl ConfigEditor {
+    pub fn new(content: &str) -> Self {
+        Self {
+            lines: content.lines().map(String::from).collect(),
+        }
+    }
+
+    pub fn content(&self) -> String {
+        let mut content = self.lines.join("\n");
+        content.push('\n');
+        content
+    }
+
+    /// Append the extension to the end of the plugins or themes list
+    pub fn add(&mut self, ext_type: ExtensionType, extension: &Extension) -> Result<()> {
+        let key = list_key(ext_type);
+        let rendered = serde_yaml::to_string(&[extension])?;
+
+        match self.find_block(key)? {
+            Some(block) => {
+                let indent = self.item_indent(&block).unwrap_or_else(|| "  ".to_string());
+                let entry = rendered.lines().map(|line| format!("{indent}{line}"));
+                self.lines.splice(block.end..block.end, entry);
+            }
+            None => {
+                if self
+                    .lines
+                    .last()
+                    .is_some_and(|line| !line.trim().is_empty())
+                {
+                    self.lines.push(String::new());
+                }
+                self.lines.push(format!("{key}:"));
+                self.lines
+                    .extend(rendered.lines().map(|line| format!("  {line}")));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the extension with the given name, returning the list it was in
+    pub fn remove(&mut self, name: &str) -> Result<Option<ExtensionType>> {
+        for ext_type in [ExtensionType::Plugin, ExtensionType::Theme] {
+            let Some(block) = self.find_block(list_key(ext_type))? else {
+                continue;
+            };
+            let Some(indent) = self.item_indent(&block) else {
+                continue;
+            };
+
+            for (start, end) in self.items(&block, &indent) {
+                if self.item_name(start, end, &indent).as_deref() == Some(name) {
+                    self.lines.drain(start..end);
+                    return Ok(Some(ext_type));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn find_block(&mut self, key: &str) -> Result<Option<Block>> {
+        let Some(key_line) = self.lines.iter().position(|line| {
+            line.strip_prefix(key)
+                .is_some_and(|rest| rest.starts_with(':'))
+        }) else {
+            return Ok(None);
+        };
+
+        let rest = &self.lines[key_line][key.len() + 1..];
+        let (value, comment) = match rest.find(" #") {
+            Some(index) => (rest[..index].trim(), &rest[index..]),
+            None => (rest.trim(), ""),
+        };
+        match value {
+            "" => {}
+            _ if value.starts_with('#') => {}
+            // An empty list written inline becomes a block list
+            "[]" | "~" | "null" => self.lines[key_line] = format!("{key}:{comment}"),
+            _ => {
+                return Err(RexerError::InvalidConfig(format!(
+                    "{key} is written as an inline list, edit it with rex edit"
+                )))
+            }
+        }
+
+        let mut end = key_line + 1;
+        for (index, line) in self.lines.iter().enumerate().skip(key_line + 1) {
+            if line.starts_with([' ', '\t', '-']) {
+                end = index + 1;
+            } else if !line.trim().is_empty() && !line.starts_with('#') {
+                break;
+            }
+        }
+
+        Ok(Some(Block { key_line, end }))
+    }
+
+    /// Indentation of the list items, None when the list has no items yet
+    fn item_indent(&self, block: &Block) -> Option<String> {
+        self.lines[block.key_line + 1..block.end]
+            .iter()
+            .find(|line| {
+                let trimmed = line.trim_start();
+                trimmed == "-" || trimmed.starts_with("- ")
+            })
+            .map(|line| line[..line.len() - line.trim_start().len()].to_string())
+    }
+
+    /// Line ranges of the list items, without the blank lines and comments after them
+    fn items(&self, block: &Block, indent: &str) -> Vec<(usize, usize)> {
+        let is_item_start = |line: &String| {
+            line.strip_prefix(indent)
+                .is_some_and(|rest| rest == "-" || rest.starts_with("- "))
+        };
+
+        let starts: Vec<usize> = (block.key_line + 1..block.end)
+            .filter(|&index| is_item_start(&self.lines[index]))
+            .collect();
+
+        starts
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| {
+                let mut end = starts.get(i + 1).copied().unwrap_or(block.end);
+                while end > start + 1 {
+                    let line = &self.lines[end - 1];
+                    let indented = line.len() - line.trim_start().len() > indent.len();
+                    if line.trim().is_empty() || (line.trim_start().starts_with('#') && !indented) {
+                        end -= 1;
+                    } else {
+                        break;
+                    }
+                }
+                (start, end)
+            })
+            .collect()
+    }
+
+    fn item_name(&self, start: usize, end: usize, indent: &str) -> Option<String> {
+        let item = self.lines[start..end]
+            .iter()
+            .map(|line| line.strip_prefix(indent).unwrap_or(line))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let value: serde_yaml::Value = serde_yaml::from_str(&item).ok()?;
+        value.get(0)?.get("name")?.as_str().map(String::from)
+    }
+}
+
+fn list_key(ext_type: ExtensionType) -> &'static str {
+    match ext_type {
+        ExtensionType::Plugin => "plugins",
+        ExtensionType::Theme => "themes",
+    }
+}
@@ -6,15 +6,41 @@ pub enum RexerError {
     ConfigNotFound(String),
 
     #[error("Invalid configuration: {0}")]
-    #[allow(dead_code)]
     InvalidConfig(String),
 
     #[error("Git operation failed: {0}")]
     GitError(String),
 
+    #[error("Reference not found: {0}")]
+    ReferenceNotFound(String),
+
     #[error("Extension not found: {0}")]
     ExtensionNotFound(String),
 
+    #[error("Environment not found: {0}")]
+    EnvironmentNotFound(String),
+
+    #[error("Archive error: {0}")]
+    ArchiveError(String),
+
+    #[error("Hook failed: {0}")]
+    HookError(String),
+
+    #[error("Plugin dependency error: {0}")]
+    DependencyError(String),
+
+    #[error("Incompatible with this Redmine: {0}")]
+    CompatibilityError(String),
+
+    #[error("Offline install failed: {0}")]
+    OfflineError(String),
+
+    #[error("Invalid package: {0}")]
+    PackageError(String),
+
+    #[error("Local modifications: {0}")]
+    LocalModifications(String),
+
     #[error("Lock file error: {0}")]
     LockFileError(String),
 
@@ -1,42 +1,213 @@
+use crate::error::{Result, RexerError};
+use crate::version::VersionConstraint;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Environment used for the top-level plugins and themes
+pub const DEFAULT_ENV: &str = "default";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Extension {
     pub name: String,
     #[serde(flatten)]
     pub source: Source,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Hooks>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Shell commands run after an extension is installed, uninstalled or updated
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Hooks {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installed: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uninstalled: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Source {
     #[serde(rename = "git")]
     Git {
         url: String,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        branch: Option<String>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        tag: Option<String>,
-        #[serde(skip_serializing_if = "Option::is_none")]
-        commit: Option<String>,
+        #[serde(flatten)]
+        options: GitOptions,
     },
     #[serde(rename = "github")]
     GitHub {
+        repo: String,
+        #[serde(flatten)]
+        options: GitOptions,
+    },
+    #[serde(rename = "gitlab")]
+    GitLab {
+        repo: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        host: Option<String>,
+        #[serde(flatten)]
+        options: GitOptions,
+    },
+    #[serde(rename = "bitbucket")]
+    Bitbucket {
         repo: String,
         #[serde(skip_serializing_if = "Option::is_none")]
-        branch: Option<String>,
+        host: Option<String>,
+        #[serde(flatten)]
+        options: GitOptions,
+    },
+    #[serde(rename = "gitea")]
+    Gitea {
+        repo: String,
         #[serde(skip_serializing_if = "Option::is_none")]
-        tag: Option<String>,
+        host: Option<String>,
+        #[serde(flatten)]
+        options: GitOptions,
+    },
+    #[serde(rename = "path")]
+    Path {
+        path: String,
+        #[serde(default, skip_serializing_if = "LinkMode::is_symlink")]
+        link: LinkMode,
+    },
+    #[serde(rename = "archive")]
+    Archive {
+        url: String,
+        sha256: String,
         #[serde(skip_serializing_if = "Option::is_none")]
-        commit: Option<String>,
+        strip_components: Option<usize>,
     },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Options shared by all Git based sources
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GitOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// Constraint resolved to the highest matching tag, such as `~> 1.2`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Subdirectory to install when the repository contains several extensions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// How much of the repository to clone, overriding the top-level `clone`
+    #[serde(rename = "clone", skip_serializing_if = "Option::is_none")]
+    pub clone_mode: Option<CloneMode>,
+    /// Submodules to check out with the repository
+    #[serde(default, skip_serializing_if = "Submodules::is_off")]
+    pub submodules: Submodules,
+}
+
+impl GitOptions {
+    pub fn reference(&self) -> Option<String> {
+        self.branch
+            .clone()
+            .or_else(|| self.tag.clone())
+            .or_else(|| self.commit.clone())
+    }
+}
+
+/// How much of a Git repository is cloned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CloneMode {
+    /// The whole history
+    #[default]
+    Full,
+    /// Only the checked out commit, deepened when another reference needs more
+    Shallow,
+    /// The whole history without file contents, which are fetched on checkout
+    Partial,
+}
+
+/// Which submodules are checked out, written `true` or `recursive` in the configuration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "SubmodulesValue", into = "SubmodulesValue")]
+pub enum Submodules {
+    #[default]
+    Off,
+    /// The submodules of the repository itself
+    On,
+    /// Also the submodules of submodules
+    Recursive,
+}
+
+impl Submodules {
+    fn is_off(&self) -> bool {
+        matches!(self, Submodules::Off)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum SubmodulesValue {
+    Enabled(bool),
+    Mode(String),
+}
+
+impl TryFrom<SubmodulesValue> for Submodules {
+    type Error = String;
+
+    fn try_from(value: SubmodulesValue) -> std::result::Result<Self, Self::Error> {
+        match value {
+            SubmodulesValue::Enabled(false) => Ok(Submodules::Off),
+            SubmodulesValue::Enabled(true) => Ok(Submodules::On),
+            SubmodulesValue::Mode(mode) if mode == "recursive" => Ok(Submodules::Recursive),
+            SubmodulesValue::Mode(mode) => Err(format!(
+                "invalid submodules `{mode}`, expected true, false or recursive"
+            )),
+        }
+    }
+}
+
+impl From<Submodules> for SubmodulesValue {
+    fn from(submodules: Submodules) -> Self {
+        match submodules {
+            Submodules::Off => SubmodulesValue::Enabled(false),
+            Submodules::On => SubmodulesValue::Enabled(true),
+            Submodules::Recursive => SubmodulesValue::Mode("recursive".to_string()),
+        }
+    }
+}
+
+/// How a local path source is placed into the Redmine root
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkMode {
+    #[default]
+    Symlink,
+    Copy,
+}
+
+impl LinkMode {
+    fn is_symlink(&self) -> bool {
+        matches!(self, LinkMode::Symlink)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ExtensionsConfig {
     #[serde(default)]
     pub plugins: Vec<Extension>,
     #[serde(default)]
     pub themes: Vec<Extension>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub environments: BTreeMap<String, EnvironmentConfig>,
+    /// Clone mode of the Git sources that don't set their own
+    #[serde(rename = "clone", default, skip_serializing_if = "Option::is_none")]
+    pub clone_mode: Option<CloneMode>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnvironmentConfig {
+    #[serde(default)]
+    pub plugins: Vec<Extension>,
+    #[serde(default)]
+    pub themes: Vec<Extension>,
 }
 
 impl ExtensionsConfig {
@@ -46,6 +217,44 @@ impl ExtensionsConfig {
             .map(|e| (e, ExtensionType::Plugin))
             .chain(self.themes.iter().map(|e| (e, ExtensionType::Theme)))
     }
+
+    /// Names of all environments, starting with the default one
+    pub fn env_names(&self) -> Vec<&str> {
+        std::iter::once(DEFAULT_ENV)
+            .chain(
+                self.environments
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|name| *name != DEFAULT_ENV),
+            )
+            .collect()
+    }
+
+    /// Build a configuration containing only the extensions of the given environment,
+    /// with the top-level clone mode applied to their Git sources
+    pub fn for_env(&self, env: &str) -> Option<ExtensionsConfig> {
+        let (mut plugins, mut themes) = if env == DEFAULT_ENV {
+            (self.plugins.clone(), self.themes.clone())
+        } else {
+            let env_config = self.environments.get(env)?;
+            (env_config.plugins.clone(), env_config.themes.clone())
+        };
+
+        if let Some(clone_mode) = self.clone_mode {
+            for extension in plugins.iter_mut().chain(themes.iter_mut()) {
+                if let Some(options) = extension.source.git_options_mut() {
+                    options.clone_mode.get_or_insert(clone_mode);
+                }
+            }
+        }
+
+        Some(ExtensionsConfig {
+            plugins,
+            themes,
+            environments: BTreeMap::new(),
+            clone_mode: None,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -56,9 +265,15 @@ pub enum ExtensionType {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LockFile {
+    #[serde(default = "default_env")]
+    pub env: String,
     pub extensions: Vec<LockedExtension>,
 }
 
+fn default_env() -> String {
+    DEFAULT_ENV.to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LockedExtension {
     pub name: String,
@@ -66,6 +281,35 @@ pub struct LockedExtension {
     pub source: Source,
     pub commit_hash: Option<String>,
     pub installed_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hooks: Option<Hooks>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    /// Tag chosen for a version constraint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_tag: Option<String>,
+    /// Checked out commit of each submodule, by path
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub submodule_commits: BTreeMap<String, String>,
+}
+
+impl LockedExtension {
+    /// The source pinned to the recorded commit, so fetching it reproduces this entry
+    pub fn pinned_source(&self) -> Source {
+        let mut source = self.source.clone();
+        if let (Some(options), Some(commit)) = (source.git_options_mut(), &self.commit_hash) {
+            *options = GitOptions {
+                commit: Some(commit.clone()),
+                path: options.path.take(),
+                clone_mode: options.clone_mode,
+                submodules: options.submodules,
+                ..Default::default()
+            };
+        }
+        source
+    }
 }
 
 impl Source {
@@ -75,29 +319,90 @@ impl Source {
             Source::GitHub { repo, .. } => {
                 format!("https://github.com/{repo}.git")
             }
+            Source::GitLab { repo, host, .. } => hosted_url(host, "gitlab.com", repo),
+            Source::Bitbucket { repo, host, .. } => hosted_url(host, "bitbucket.org", repo),
+            Source::Gitea { repo, host, .. } => hosted_url(host, "gitea.com", repo),
+            Source::Path { path, .. } => path.clone(),
+            Source::Archive { url, .. } => url.clone(),
+        }
+    }
+
+    /// Options of Git based sources, None for local paths and archives
+    pub fn git_options(&self) -> Option<&GitOptions> {
+        match self {
+            Source::Git { options, .. }
+            | Source::GitHub { options, .. }
+            | Source::GitLab { options, .. }
+            | Source::Bitbucket { options, .. }
+            | Source::Gitea { options, .. } => Some(options),
+            Source::Path { .. } | Source::Archive { .. } => None,
+        }
+    }
+
+    pub fn git_options_mut(&mut self) -> Option<&mut GitOptions> {
+        match self {
+            Source::Git { options, .. }
+            | Source::GitHub { options, .. }
+            | Source::GitLab { options, .. }
+            | Source::Bitbucket { options, .. }
+            | Source::Gitea { options, .. } => Some(options),
+            Source::Path { .. } | Source::Archive { .. } => None,
         }
     }
 
     pub fn reference(&self) -> Option<String> {
+        self.git_options().and_then(GitOptions::reference)
+    }
+
+    /// Check the option combinations that deserializing doesn't catch
+    pub fn validate(&self) -> Result<()> {
+        if let Some(options) = self.git_options() {
+            let references = [
+                &options.branch,
+                &options.tag,
+                &options.commit,
+                &options.version,
+            ]
+            .iter()
+            .filter(|reference| reference.is_some())
+            .count();
+            if references > 1 {
+                return Err(RexerError::InvalidConfig(
+                    "only one of branch, tag, commit or version can be set".to_string(),
+                ));
+            }
+
+            if let Some(version) = &options.version {
+                VersionConstraint::parse(version)?;
+            }
+        }
+
         match self {
-            Source::Git {
-                branch,
-                tag,
-                commit,
-                ..
-            } => branch
-                .clone()
-                .or_else(|| tag.clone())
-                .or_else(|| commit.clone()),
-            Source::GitHub {
-                branch,
-                tag,
-                commit,
-                ..
-            } => branch
-                .clone()
-                .or_else(|| tag.clone())
-                .or_else(|| commit.clone()),
+            Source::GitHub { repo, .. }
+            | Source::GitLab { repo, .. }
+            | Source::Bitbucket { repo, .. }
+            | Source::Gitea { repo, .. }
+                if repo.split('/').count() < 2 || repo.split('/').any(str::is_empty) =>
+            {
+                Err(RexerError::InvalidConfig(format!(
+                    "repo must be written as owner/name: {repo}"
+                )))
+            }
+            _ => Ok(()),
         }
     }
 }
+
+/// Expand an `owner/repo` shorthand to a clone URL on the given or default host
+fn hosted_url(host: &Option<String>, default_host: &str, repo: &str) -> String {
+    let host = host
+        .as_deref()
+        .unwrap_or(default_host)
+        .trim_end_matches('/');
+
+    if host.contains("://") {
+        format!("{host}/{repo}.git")
+    } else {
+        format!("https://{host}/{repo}.git")
+    }
+}
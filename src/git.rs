@@ -1,6 +1,10 @@
+use crate::cache::{self, MirrorCache};
 use crate::error::{Result, RexerError};
-use crate::extension::Source;
+use crate::extension::{CloneMode, GitOptions, Source, Submodules};
+use crate::local::BUNDLER_LOCK;
 use log::{debug, info};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 use std::process::Command;
 
@@ -58,20 +62,64 @@ impl GitManager {
 
         Ok(())
     }
-    pub fn clone_or_update(source: &Source, destination: &Path) -> Result<String> {
-        if destination.exists() {
-            Self::update_repository(source, destination)
+    /// Clone or update the repository and check out its submodules if the source asks
+    /// for them. Offline, it is only read from the mirror cache.
+    pub fn clone_or_update(source: &Source, destination: &Path, offline: bool) -> Result<String> {
+        let commit_hash = if destination.join(".git").exists() {
+            Self::update_repository(source, destination, offline)?
+        } else if destination.exists() {
+            // Git would otherwise work on the repository around it, such as the Redmine checkout
+            return Err(RexerError::GitError(format!(
+                "{} is not a git repository, reinstall it with rex reinstall",
+                destination.display()
+            )));
         } else {
-            Self::clone_repository(source, destination)
+            Self::clone_repository(source, destination, offline)?
+        };
+
+        if let Some(options) = source.git_options() {
+            Self::update_submodules(destination, options.submodules)?;
         }
+
+        Ok(commit_hash)
     }
 
-    fn clone_repository(source: &Source, destination: &Path) -> Result<String> {
+    fn clone_repository(source: &Source, destination: &Path, offline: bool) -> Result<String> {
         let url = source.full_url();
+        let clone_mode = Self::clone_mode(source);
         info!("Cloning {} to {}", url, destination.display());
+        let location = Self::clone_location(&url, clone_mode, offline)?;
+
+        // A shallow clone of a reference fetches just its commit into an empty repository
+        if let (CloneMode::Shallow, Some(options), Some(_)) =
+            (clone_mode, source.git_options(), source.reference())
+        {
+            Self::run_git_command_status(&["init", "-q", &destination.to_string_lossy()], None)?;
+            Self::run_git_command_status(&["remote", "add", "origin", &url], Some(destination))?;
+            Self::fetch_shallow(destination, options, &url, &location)?;
+            return Self::get_current_commit_hash(destination);
+        }
 
-        // Clone the repository
-        Self::run_git_command_status(&["clone", &url, &destination.to_string_lossy()], None)?;
+        // Clone the repository, from the shared mirror when there is one. Like a
+        // remote, the mirror only hands out the commits its branches and tags reach.
+        let destination_arg = destination.to_string_lossy();
+        let mut args = vec!["clone"];
+        match clone_mode {
+            CloneMode::Full => {}
+            CloneMode::Shallow => args.extend(["--depth", "1"]),
+            CloneMode::Partial => args.push("--filter=blob:none"),
+        }
+        if location == url {
+            args.extend([url.as_str(), &destination_arg]);
+            Self::run_git_command_status(&args, None)?;
+        } else {
+            args.extend(["--no-local", &location, &destination_arg]);
+            Self::run_git_command_status(&args, None)?;
+            Self::run_git_command_status(
+                &["remote", "set-url", "origin", &url],
+                Some(destination),
+            )?;
+        }
 
         // Checkout specific reference if provided
         if let Some(reference) = source.reference() {
@@ -81,12 +129,47 @@ impl GitManager {
         Self::get_current_commit_hash(destination)
     }
 
-    fn update_repository(source: &Source, destination: &Path) -> Result<String> {
+    fn clone_mode(source: &Source) -> CloneMode {
+        source
+            .git_options()
+            .and_then(|options| options.clone_mode)
+            .unwrap_or_default()
+    }
+
+    /// Shallow and partial clones skip the mirror, which always holds the whole
+    /// repository. Offline, the mirror is all there is.
+    fn clone_location(url: &str, clone_mode: CloneMode, offline: bool) -> Result<String> {
+        if matches!(clone_mode, CloneMode::Full) || offline {
+            Self::fetch_location(url, offline)
+        } else {
+            Ok(url.to_string())
+        }
+    }
+
+    fn update_repository(source: &Source, destination: &Path, offline: bool) -> Result<String> {
         let url = source.full_url();
         info!("Updating {} at {}", url, destination.display());
 
-        // Fetch latest changes from origin
-        Self::run_git_command_status(&["fetch", "origin"], Some(destination))?;
+        // Offline, a commit already in the repository needs nothing new
+        let present = offline
+            && source
+                .reference()
+                .is_some_and(|reference| Self::has_commit(destination, &reference));
+
+        let clone_mode = Self::clone_mode(source);
+        if let (false, CloneMode::Shallow, Some(options)) =
+            (present, clone_mode, source.git_options())
+        {
+            let location = Self::clone_location(&url, clone_mode, offline)?;
+            Self::fetch_shallow(destination, options, &url, &location)?;
+            return Self::get_current_commit_hash(destination);
+        }
+
+        // Fetch latest changes from origin, including tags on commits already fetched
+        if !present {
+            let location = Self::clone_location(&url, clone_mode, offline)?;
+            Self::fetch_from(destination, &url, &location)?;
+        }
 
         if let Some(reference) = source.reference() {
             Self::checkout_reference(destination, &reference)?;
@@ -98,6 +181,90 @@ impl GitManager {
         Self::get_current_commit_hash(destination)
     }
 
+    /// Fetch branches and tags from origin, or from the mirror of origin. Shallow
+    /// repositories get their whole history.
+    fn fetch_from(repo_path: &Path, url: &str, location: &str) -> Result<()> {
+        let mut args = vec!["fetch"];
+        if Self::is_shallow(repo_path) {
+            args.push("--unshallow");
+        }
+
+        if location == url {
+            args.extend(["origin", "--tags"]);
+        } else {
+            args.extend([
+                location,
+                "+refs/heads/*:refs/remotes/origin/*",
+                "+refs/tags/*:refs/tags/*",
+            ]);
+        }
+        Self::run_git_command_status(&args, Some(repo_path))
+    }
+
+    /// Fetch only the commit of the branch, tag, commit or default branch and check it
+    /// out. Commits are fetched by their hash, which some servers refuse, so the
+    /// repository is deepened to its whole history when the shallow fetch fails.
+    fn fetch_shallow(
+        repo_path: &Path,
+        options: &GitOptions,
+        url: &str,
+        location: &str,
+    ) -> Result<()> {
+        let refspec = if let Some(branch) = &options.branch {
+            format!("+refs/heads/{branch}:refs/remotes/origin/{branch}")
+        } else if let Some(tag) = &options.tag {
+            format!("+refs/tags/{tag}:refs/tags/{tag}")
+        } else if let Some(commit) = &options.commit {
+            commit.clone()
+        } else {
+            "HEAD".to_string()
+        };
+
+        let remote = Self::remote_arg(url, location);
+        if let Err(e) = Self::run_git_command_status(
+            &["fetch", "--depth", "1", &remote, &refspec],
+            Some(repo_path),
+        ) {
+            debug!("Shallow fetch of {refspec} failed, fetching the whole history: {e}");
+            Self::fetch_from(repo_path, url, location)?;
+            return match options.reference() {
+                Some(reference) => Self::checkout_reference(repo_path, &reference),
+                None => Self::checkout_default_branch(repo_path),
+            };
+        }
+
+        // Depth 1 cuts the fetched commit off its parents, so branches are moved
+        // to it rather than fast-forwarded
+        let branch = match options.reference() {
+            Some(_) => options.branch.clone(),
+            None => Self::current_branch(repo_path),
+        };
+        match branch {
+            Some(branch) => Self::run_git_command_status(
+                &["checkout", "-q", "-B", &branch, "FETCH_HEAD"],
+                Some(repo_path),
+            ),
+            None => Self::run_git_command_status(
+                &["checkout", "-q", "--detach", "FETCH_HEAD"],
+                Some(repo_path),
+            ),
+        }
+    }
+
+    /// The remote to fetch from: origin, or the path of its mirror
+    fn remote_arg(url: &str, location: &str) -> String {
+        if location == url {
+            "origin".to_string()
+        } else {
+            location.to_string()
+        }
+    }
+
+    fn is_shallow(repo_path: &Path) -> bool {
+        Self::run_git_command(&["rev-parse", "--is-shallow-repository"], Some(repo_path))
+            .is_ok_and(|output| output == "true")
+    }
+
     fn checkout_reference(repo_path: &Path, reference: &str) -> Result<()> {
         debug!("Checking out reference: {reference}");
 
@@ -132,8 +299,12 @@ impl GitManager {
             return Ok(());
         }
 
+        // The fetch went through, so a reference the repository lacks doesn't exist upstream
+        if !Self::has_commit(repo_path, reference) {
+            return Err(RexerError::ReferenceNotFound(reference.to_string()));
+        }
         Err(RexerError::GitError(format!(
-            "Reference '{reference}' not found"
+            "Failed to check out '{reference}'"
         )))
     }
 
@@ -177,32 +348,261 @@ impl GitManager {
             )?;
         }
 
-        // Pull latest changes
-        if Self::run_git_command_status(&["pull"], Some(repo_path)).is_err() {
-            // If pull fails, just log and continue - might be in detached head state
-            debug!("Pull failed, might be in detached HEAD state");
+        // Move to the fetched commit of the branch
+        let remote_branch = format!("origin/{branch_name}");
+        if Self::run_git_command_status(&["merge", "--ff-only", &remote_branch], Some(repo_path))
+            .is_err()
+        {
+            // If the merge fails, just log and continue - the branch may have diverged
+            debug!("Fast-forward to {remote_branch} failed");
         }
 
         Ok(())
     }
 
-    fn get_current_commit_hash(repo_path: &Path) -> Result<String> {
+    /// Where to fetch a repository from: its refreshed mirror in the shared cache, or
+    /// the URL itself when it has no mirror or the mirror can't be refreshed. Offline,
+    /// remote repositories must already be mirrored.
+    fn fetch_location(url: &str, offline: bool) -> Result<String> {
+        if offline {
+            return cache::offline_location(url)
+                .ok_or_else(|| RexerError::GitError(format!("{url} is not in the mirror cache")));
+        }
+
+        if !cache::is_remote(url) {
+            return Ok(url.to_string());
+        }
+        let Some(cache) = MirrorCache::open() else {
+            return Ok(url.to_string());
+        };
+
+        match cache.refresh(url) {
+            Ok(mirror) => Ok(mirror.to_string_lossy().to_string()),
+            Err(e) => {
+                debug!("Not using the mirror of {url}: {e}");
+                Ok(url.to_string())
+            }
+        }
+    }
+
+    /// Check out the submodules at the commits recorded by the checked out commit,
+    /// following URL changes in .gitmodules
+    fn update_submodules(repo_path: &Path, submodules: Submodules) -> Result<()> {
+        let recursive = match submodules {
+            Submodules::Off => return Ok(()),
+            Submodules::On => None,
+            Submodules::Recursive => Some("--recursive"),
+        };
+        debug!("Updating submodules of {}", repo_path.display());
+
+        let mut sync = vec!["submodule", "sync", "--quiet"];
+        sync.extend(recursive);
+        Self::run_git_command_status(&sync, Some(repo_path))?;
+
+        let mut update = vec!["submodule", "update", "--init", "--checkout"];
+        update.extend(recursive);
+        Self::run_git_command_status(&update, Some(repo_path))
+    }
+
+    /// Checked out commit of each initialized submodule, by path
+    pub fn submodule_commits(
+        repo_path: &Path,
+        submodules: Submodules,
+    ) -> Result<BTreeMap<String, String>> {
+        let mut args = vec!["submodule", "status"];
+        match submodules {
+            Submodules::Off => return Ok(BTreeMap::new()),
+            Submodules::On => {}
+            Submodules::Recursive => args.push("--recursive"),
+        }
+        let output = Self::run_git_command(&args, Some(repo_path))?;
+
+        // Lines look like " <commit> <path> (<describe>)", prefixed with "-" when
+        // the submodule is not initialized and "+" or "U" when it differs from the
+        // recorded commit
+        let mut commits = BTreeMap::new();
+        for line in output.lines() {
+            let line = line.trim_start();
+            if line.starts_with('-') {
+                continue;
+            }
+            let line = line.trim_start_matches(['+', 'U']);
+            let Some((commit, rest)) = line.split_once(' ') else {
+                continue;
+            };
+            let path = match rest.rsplit_once(" (") {
+                Some((path, _)) if rest.ends_with(')') => path,
+                _ => rest,
+            };
+            commits.insert(path.to_string(), commit.to_string());
+        }
+
+        Ok(commits)
+    }
+
+    /// Whether the repository contains the commit, or the commit a ref points to
+    pub fn has_commit(repo_path: &Path, commit: &str) -> bool {
+        Self::run_git_command_status(
+            &["cat-file", "-e", &format!("{commit}^{{commit}}")],
+            Some(repo_path),
+        )
+        .is_ok()
+    }
+
+    /// Create a bare mirror of the branches and tags of a repository
+    pub fn clone_mirror(url: &str, destination: &Path) -> Result<()> {
+        Self::run_git_command_status(
+            &[
+                "clone",
+                "--bare",
+                "--quiet",
+                url,
+                &destination.to_string_lossy(),
+            ],
+            None,
+        )?;
+        Self::run_git_command_status(
+            &[
+                "config",
+                "remote.origin.fetch",
+                "+refs/heads/*:refs/heads/*",
+            ],
+            Some(destination),
+        )
+    }
+
+    /// Bring a mirror created by `clone_mirror` up to date with its origin
+    pub fn fetch_mirror(mirror: &Path) -> Result<()> {
+        Self::run_git_command_status(
+            &["fetch", "--quiet", "--prune", "--tags", "origin"],
+            Some(mirror),
+        )
+    }
+
+    /// Directory name for a shared clone, readable but unique per URL
+    pub fn repository_dir_name(url: &str) -> String {
+        let name = url
+            .trim_end_matches('/')
+            .trim_end_matches(".git")
+            .rsplit(['/', ':'])
+            .next()
+            .filter(|name| !name.is_empty())
+            .unwrap_or("repo");
+        let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+
+        format!("{name}-{}", &hash[..12])
+    }
+
+    pub fn get_current_commit_hash(repo_path: &Path) -> Result<String> {
         Self::run_git_command(&["rev-parse", "HEAD"], Some(repo_path))
     }
 
-    #[allow(dead_code)]
-    pub fn get_latest_commit_hash(source: &Source) -> Result<String> {
-        // Clone to a temporary directory to get the latest hash
-        let temp_dir = tempfile::tempdir()?;
-        let temp_path = temp_dir.path();
+    /// URL of the `origin` remote, or of the first remote when there is no origin
+    pub fn remote_url(repo_path: &Path) -> Result<Option<String>> {
+        let remotes = Self::run_git_command(&["remote"], Some(repo_path))?;
+        let remote = remotes
+            .lines()
+            .find(|remote| *remote == "origin")
+            .or_else(|| remotes.lines().next());
+
+        match remote {
+            Some(remote) => {
+                Self::run_git_command(&["remote", "get-url", remote], Some(repo_path)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
 
-        let url = source.full_url();
-        Self::run_git_command_status(&["clone", &url, &temp_path.to_string_lossy()], None)?;
+    /// Name of the checked out branch, None in detached HEAD state
+    pub fn current_branch(repo_path: &Path) -> Option<String> {
+        Self::run_git_command(&["symbolic-ref", "--short", "-q", "HEAD"], Some(repo_path)).ok()
+    }
 
-        if let Some(reference) = source.reference() {
-            Self::checkout_reference(temp_path, &reference)?;
+    /// Tag pointing at the checked out commit, if any
+    pub fn current_tag(repo_path: &Path) -> Option<String> {
+        Self::run_git_command(
+            &["describe", "--tags", "--exact-match", "HEAD"],
+            Some(repo_path),
+        )
+        .ok()
+    }
+
+    /// Changed and untracked files in a working tree
+    pub fn modified_files(repo_path: &Path) -> Result<Vec<String>> {
+        let output = Self::run_git_command(
+            &["status", "--porcelain", "--untracked-files=all"],
+            Some(repo_path),
+        )?;
+
+        // Each line is a two letter status and the path, the output is trimmed so the
+        // first status may have lost its leading space. Renames list both paths.
+        Ok(output
+            .lines()
+            .filter_map(|line| line.trim_start().split_once(' '))
+            .map(|(_, path)| {
+                let path = path.trim_start();
+                path.rsplit_once(" -> ")
+                    .map_or(path, |(_, new_path)| new_path)
+            })
+            .map(|path| path.trim_matches('"').to_string())
+            .collect())
+    }
+
+    /// Throw away uncommitted changes and untracked files, keeping the Gemfile.lock
+    /// written by bundle install
+    pub fn discard_changes(repo_path: &Path) -> Result<()> {
+        Self::run_git_command_status(&["reset", "-q", "--hard"], Some(repo_path))?;
+        Self::run_git_command_status(
+            &["clean", "-q", "-fd", "-e", &format!("/{BUNDLER_LOCK}")],
+            Some(repo_path),
+        )
+    }
+
+    /// List the branches and tags of a remote repository without cloning it
+    pub fn ls_remote(url: &str) -> Result<RemoteRefs> {
+        info!("Listing refs of {url}");
+        let output = Self::run_git_command(&["ls-remote", "--sort=-version:refname", url], None)?;
+
+        let mut refs = RemoteRefs::default();
+        for line in output.lines() {
+            let Some((hash, name)) = line.split_once('\t') else {
+                continue;
+            };
+
+            if name == "HEAD" {
+                refs.head = Some(hash.to_string());
+            } else if let Some(branch) = name.strip_prefix("refs/heads/") {
+                refs.branches.insert(branch.to_string(), hash.to_string());
+            } else if let Some(tag) = name.strip_prefix("refs/tags/") {
+                // Annotated tags are listed twice, "^{}" marks the commit they point to
+                let (tag, peeled) = match tag.strip_suffix("^{}") {
+                    Some(tag) => (tag, true),
+                    None => (tag, false),
+                };
+                match refs.tags.iter_mut().find(|(name, _)| name == tag) {
+                    Some(entry) if peeled => entry.1 = hash.to_string(),
+                    Some(_) => {}
+                    None => refs.tags.push((tag.to_string(), hash.to_string())),
+                }
+            }
         }
 
-        Self::get_current_commit_hash(temp_path)
+        Ok(refs)
+    }
+}
+
+/// Branches and tags advertised by a remote repository
+#[derive(Debug, Default)]
+pub struct RemoteRefs {
+    /// Commit of the default branch
+    pub head: Option<String>,
+    pub branches: HashMap<String, String>,
+    /// Tags and their commits, highest version first
+    pub tags: Vec<(String, String)>,
+}
+
+impl RemoteRefs {
+    pub fn newest_tag(&self) -> Option<&str> {
+        self.tags.first().map(|(name, _)| name.as_str())
     }
 }
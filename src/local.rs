@@ -0,0 +1,156 @@
+use crate::error::{Result, RexerError};
+use crate::extension::LinkMode;
+use log::{debug, info};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Written into plugin directories by `bundle install`, so it is not part of the content
+pub const BUNDLER_LOCK: &str = "Gemfile.lock";
+
+pub struct LocalManager;
+
+impl LocalManager {
+    /// Resolve a local source path, relative paths being based on the Redmine root
+    pub fn resolve_path(redmine_root: &Path, path: &str) -> Result<PathBuf> {
+        let full_path = redmine_root.join(path);
+
+        let resolved = full_path.canonicalize().map_err(|e| {
+            RexerError::InvalidConfig(format!("Path {} not found: {e}", full_path.display()))
+        })?;
+
+        if !resolved.is_dir() {
+            return Err(RexerError::InvalidConfig(format!(
+                "Path {} is not a directory",
+                resolved.display()
+            )));
+        }
+
+        Ok(resolved)
+    }
+
+    /// Link or copy the source directory to the destination and return its content digest
+    pub fn sync(source_dir: &Path, link: LinkMode, destination: &Path) -> Result<String> {
+        info!(
+            "Syncing {} to {} ({:?})",
+            source_dir.display(),
+            destination.display(),
+            link
+        );
+
+        Self::remove(destination)?;
+
+        match link {
+            LinkMode::Symlink => Self::symlink_dir(source_dir, destination)?,
+            LinkMode::Copy => Self::copy_dir(source_dir, destination)?,
+        }
+
+        Self::digest(source_dir)
+    }
+
+    /// Remove a directory or symlink at the given path if present
+    pub fn remove(path: &Path) -> Result<()> {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => fs::remove_file(path)?,
+            Ok(_) => fs::remove_dir_all(path)?,
+            Err(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Compute a SHA-256 digest over the relative paths and contents of all files,
+    /// ignoring the .git directory and the Gemfile.lock written by `bundle install`
+    pub fn digest(dir: &Path) -> Result<String> {
+        let mut files = Vec::new();
+        Self::collect_files(dir, dir, &mut files)?;
+        files.sort();
+
+        let mut hasher = Sha256::new();
+        for relative_path in &files {
+            let path = dir.join(relative_path);
+            hasher.update(relative_path.to_string_lossy().as_bytes());
+            hasher.update([0]);
+
+            let metadata = fs::symlink_metadata(&path)?;
+            if metadata.file_type().is_symlink() {
+                hasher.update(fs::read_link(&path)?.to_string_lossy().as_bytes());
+            } else {
+                hasher.update(fs::read(&path)?);
+            }
+            hasher.update([0]);
+        }
+
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_name() == ".git" || (dir == root && entry.file_name() == BUNDLER_LOCK) {
+                continue;
+            }
+
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                Self::collect_files(root, &path, files)?;
+            } else if let Ok(relative_path) = path.strip_prefix(root) {
+                files.push(relative_path.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    fn copy_dir(source_dir: &Path, destination: &Path) -> Result<()> {
+        fs::create_dir_all(destination)?;
+
+        for entry in fs::read_dir(source_dir)? {
+            let entry = entry?;
+            if entry.file_name() == ".git" {
+                continue;
+            }
+
+            let source_path = entry.path();
+            let dest_path = destination.join(entry.file_name());
+            let file_type = entry.file_type()?;
+
+            if file_type.is_dir() {
+                Self::copy_dir(&source_path, &dest_path)?;
+            } else if file_type.is_symlink() {
+                let target = fs::read_link(&source_path)?;
+                debug!(
+                    "Copying symlink {} -> {}",
+                    dest_path.display(),
+                    target.display()
+                );
+                Self::symlink_file(&target, &dest_path)?;
+            } else {
+                fs::copy(&source_path, &dest_path)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn symlink_dir(target: &Path, link: &Path) -> Result<()> {
+        std::os::unix::fs::symlink(target, link)?;
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    fn symlink_dir(target: &Path, link: &Path) -> Result<()> {
+        std::os::windows::fs::symlink_dir(target, link)?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn symlink_file(target: &Path, link: &Path) -> Result<()> {
+        std::os::unix::fs::symlink(target, link)?;
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    fn symlink_file(target: &Path, link: &Path) -> Result<()> {
+        std::os::windows::fs::symlink_file(target, link)?;
+        Ok(())
+    }
+}
@@ -1,9 +1,16 @@
+mod archive;
+mod cache;
 mod cli;
 mod commands;
 mod config;
+mod editor;
 mod error;
 mod extension;
 mod git;
+mod local;
+mod plugin;
+mod validation;
+mod version;
 
 use clap::Parser;
 use cli::Cli;
@@ -0,0 +1,326 @@
+use crate::error::Result;
+use std::cmp::Ordering;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// What a Redmine plugin declares about itself in its init.rb
+#[derive(Debug, Default)]
+pub struct PluginInfo {
+    /// Identifier given to `Redmine::Plugin.register`, usually the directory name
+    pub id: Option<String>,
+    pub version: Option<String>,
+    /// Redmine versions the plugin runs on, from `requires_redmine`
+    pub requires_redmine: Option<VersionRequirement>,
+    pub requires_plugins: Vec<PluginRequirement>,
+}
+
+/// A `requires_redmine_plugin` declaration
+#[derive(Debug)]
+pub struct PluginRequirement {
+    pub id: String,
+    pub version: VersionRequirement,
+}
+
+/// The versions accepted by `requires_redmine_plugin` and `requires_redmine`
+#[derive(Debug, Clone, PartialEq)]
+pub enum VersionRequirement {
+    Any,
+    OrHigher(String),
+    /// Versions matched on their given components, so `5.1` accepts `5.1.2`
+    OneOf(Vec<String>),
+    /// A Ruby range such as `'4.0'..'5.1'`, the upper end also matched on its components
+    Between(String, String),
+}
+
+impl PluginInfo {
+    /// Read init.rb of the plugin directory. Plugins without one declare nothing.
+    pub fn read(plugin_dir: &Path) -> Result<Self> {
+        let init_rb = plugin_dir.join("init.rb");
+        if !init_rb.exists() {
+            return Ok(Self::default());
+        }
+
+        Ok(Self::parse(&fs::read_to_string(init_rb)?))
+    }
+
+    pub fn parse(source: &str) -> Self {
+        let mut info = Self::default();
+
+        for statement in statements(source) {
+            if let Some(args) = call_args(&statement, "Redmine::Plugin.register") {
+                info.id = match tokens(args).first() {
+                    Some(Token::Value(id)) => Some(id.clone()),
+                    _ => None,
+                };
+            } else if let Some(args) = call_args(&statement, "version") {
+                info.version = match tokens(args).first() {
+                    Some(Token::Value(version))
+                        if version.starts_with(|c: char| c.is_ascii_digit()) =>
+                    {
+                        Some(version.clone())
+                    }
+                    _ => None,
+                };
+            } else if let Some(args) = call_args(&statement, "requires_redmine") {
+                info.requires_redmine = Some(VersionRequirement::parse(&tokens(args)));
+            } else if let Some(args) = call_args(&statement, "requires_redmine_plugin") {
+                let tokens = tokens(args);
+                if let Some(Token::Value(id)) = tokens.first() {
+                    info.requires_plugins.push(PluginRequirement {
+                        id: id.clone(),
+                        version: VersionRequirement::parse(&tokens[1..]),
+                    });
+                }
+            }
+        }
+
+        info
+    }
+}
+
+impl VersionRequirement {
+    /// Parse the arguments after the plugin name, such as `version_or_higher: '1.0'`
+    fn parse(tokens: &[Token]) -> Self {
+        match tokens {
+            [Token::Value(version), ..] => Self::OrHigher(version.clone()),
+            [Token::Key(key), Token::Value(first), Token::Value(range), Token::Value(last), ..]
+                if key == "version" && (range == ".." || range == "...") =>
+            {
+                Self::Between(first.clone(), last.clone())
+            }
+            [Token::Key(key), Token::Value(version), ..] if key == "version_or_higher" => {
+                Self::OrHigher(version.clone())
+            }
+            [Token::Key(key), Token::Value(version), ..] if key == "version" => {
+                Self::OneOf(vec![version.clone()])
+            }
+            [Token::Key(key), Token::Open, rest @ ..] if key == "version" => Self::OneOf(
+                rest.iter()
+                    .map_while(|token| match token {
+                        Token::Value(version) => Some(version.clone()),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            _ => Self::Any,
+        }
+    }
+
+    pub fn matches(&self, version: &str) -> bool {
+        match self {
+            Self::Any => true,
+            Self::OrHigher(required) => compare_versions(version, required) != Ordering::Less,
+            Self::OneOf(versions) => versions
+                .iter()
+                .any(|accepted| compare_prefix(version, accepted) == Ordering::Equal),
+            Self::Between(first, last) => {
+                compare_versions(version, first) != Ordering::Less
+                    && compare_prefix(version, last) != Ordering::Greater
+            }
+        }
+    }
+}
+
+impl fmt::Display for VersionRequirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any version"),
+            Self::OrHigher(version) => write!(f, "{version} or higher"),
+            Self::OneOf(versions) => write!(f, "version {}", versions.join(" or ")),
+            Self::Between(first, last) => write!(f, "versions {first} to {last}"),
+        }
+    }
+}
+
+/// Compare dotted versions the way Redmine does, by their numeric components
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (version_parts(a), version_parts(b));
+    let len = a.len().max(b.len());
+    a.resize(len, 0);
+    b.resize(len, 0);
+    a.cmp(&b)
+}
+
+/// Compare only the components given in `accepted`, so `5.1.2` equals `5.1`
+fn compare_prefix(version: &str, accepted: &str) -> Ordering {
+    let accepted = version_parts(accepted);
+    let mut version = version_parts(version);
+    version.resize(accepted.len(), 0);
+    version.cmp(&accepted)
+}
+
+fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .split('.')
+        .map(|part| {
+            let digits: String = part.chars().take_while(char::is_ascii_digit).collect();
+            digits.parse().unwrap_or(0)
+        })
+        .collect()
+}
+
+/// The Redmine version declared in lib/redmine/version.rb of the Redmine root, None
+/// when rex doesn't run in a Redmine checkout
+pub fn redmine_version(redmine_root: &Path) -> Result<Option<String>> {
+    let version_rb = redmine_root.join("lib/redmine/version.rb");
+    if !version_rb.exists() {
+        return Ok(None);
+    }
+
+    let source = fs::read_to_string(version_rb)?;
+    let constant = |name: &str| {
+        statements(&source).iter().find_map(|statement| {
+            let value = statement
+                .strip_prefix(name)?
+                .trim_start()
+                .strip_prefix('=')?;
+            value.trim().parse::<u64>().ok()
+        })
+    };
+
+    Ok(
+        match (constant("MAJOR"), constant("MINOR"), constant("TINY")) {
+            (Some(major), Some(minor), tiny) => {
+                Some(format!("{major}.{minor}.{}", tiny.unwrap_or(0)))
+            }
+            _ => None,
+        },
+    )
+}
+
+/// Ruby statements without comments, joining the lines of calls whose arguments
+/// continue on the next line
+fn statements(source: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+
+    for line in source.lines() {
+        let line = strip_comment(line).trim();
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(line);
+
+        let open = current.matches(['(', '[', '{']).count();
+        let close = current.matches([')', ']', '}']).count();
+        if current.ends_with(',') || open > close {
+            continue;
+        }
+        statements.push(std::mem::take(&mut current));
+    }
+    if !current.is_empty() {
+        statements.push(current);
+    }
+
+    statements
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '#') => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Arguments of a call to the method, with or without parentheses
+fn call_args<'a>(statement: &'a str, method: &str) -> Option<&'a str> {
+    let rest = statement.strip_prefix(method)?;
+    if rest.starts_with('(') || rest.starts_with(char::is_whitespace) {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    /// A string, symbol or bare word
+    Value(String),
+    /// A hash key, written `key:` or `:key =>`
+    Key(String),
+    Open,
+    Close,
+}
+
+/// Split Ruby call arguments into the literals rex cares about
+fn tokens(args: &str) -> Vec<Token> {
+    let chars: Vec<char> = args.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    let is_word = |c: char| c.is_alphanumeric() || matches!(c, '_' | '.');
+    while index < chars.len() {
+        let c = chars[index];
+        let value = match c {
+            '\'' | '"' => {
+                let end = (index + 1..chars.len())
+                    .find(|&end| chars[end] == c)
+                    .unwrap_or(chars.len());
+                let value: String = chars[index + 1..end].iter().collect();
+                index = end + 1;
+                value
+            }
+            ':' if chars.get(index + 1).is_some_and(|&c| is_word(c)) => {
+                let end = (index + 1..chars.len())
+                    .find(|&end| !is_word(chars[end]))
+                    .unwrap_or(chars.len());
+                let value: String = chars[index + 1..end].iter().collect();
+                index = end;
+                value
+            }
+            '[' => {
+                tokens.push(Token::Open);
+                index += 1;
+                continue;
+            }
+            ']' => {
+                tokens.push(Token::Close);
+                index += 1;
+                continue;
+            }
+            // `do` ends the arguments of a call with a block
+            _ if is_word(c) => {
+                let end = (index..chars.len())
+                    .find(|&end| !is_word(chars[end]))
+                    .unwrap_or(chars.len());
+                let word: String = chars[index..end].iter().collect();
+                if word == "do" {
+                    break;
+                }
+                index = end;
+                if chars.get(index) == Some(&':') && chars.get(index + 1) != Some(&':') {
+                    index += 1;
+                    tokens.push(Token::Key(word));
+                    continue;
+                }
+                word
+            }
+            _ => {
+                index += 1;
+                continue;
+            }
+        };
+
+        // `:key =>` and `"key":` are hash keys too
+        let rest: String = chars[index..].iter().collect();
+        let rest = rest.trim_start();
+        if rest.starts_with("=>") {
+            index = chars.len() - rest.chars().count() + 2;
+            tokens.push(Token::Key(value));
+        } else if rest.starts_with(':') && !rest.starts_with("::") {
+            index = chars.len() - rest.chars().count() + 1;
+            tokens.push(Token::Key(value));
+        } else {
+            tokens.push(Token::Value(value));
+        }
+    }
+
+    tokens
+}
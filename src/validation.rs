@@ -0,0 +1,388 @@
+use crate::error::{Result, RexerError};
+use crate::extension::{Extension, ExtensionsConfig, DEFAULT_ENV};
+use serde_yaml::Value;
+use std::collections::{HashMap, HashSet};
+
+const SOURCE_KEYS: [&str; 7] = [
+    "git",
+    "github",
+    "gitlab",
+    "bitbucket",
+    "gitea",
+    "path",
+    "archive",
+];
+const GIT_OPTION_KEYS: [&str; 7] = [
+    "branch",
+    "tag",
+    "commit",
+    "version",
+    "path",
+    "clone",
+    "submodules",
+];
+const HOOK_KEYS: [&str; 3] = ["installed", "uninstalled", "updated"];
+
+/// Parse the extensions configuration and check it, reporting every problem with
+/// its line and column
+pub fn parse_extensions_config(file_name: &str, content: &str) -> Result<ExtensionsConfig> {
+    let value: Value = serde_yaml::from_str(content)
+        .map_err(|e| RexerError::InvalidConfig(Issue::from_yaml(&e).format(file_name)))?;
+
+    let mut checker = Checker {
+        locator: Locator::new(content),
+        issues: Vec::new(),
+    };
+    checker.check_document(&value);
+
+    if checker.issues.is_empty() {
+        match serde_yaml::from_str(content) {
+            Ok(config) => return Ok(config),
+            Err(e) => checker.issues.push(Issue::from_yaml(&e)),
+        }
+    }
+
+    let report = checker
+        .issues
+        .iter()
+        .map(|issue| issue.format(file_name))
+        .collect::<Vec<_>>()
+        .join("\n");
+    Err(RexerError::InvalidConfig(report))
+}
+
+/// A problem found in the configuration, at a 1-based line and column when known
+struct Issue {
+    position: Option<(usize, usize)>,
+    message: String,
+}
+
+impl Issue {
+    fn from_yaml(error: &serde_yaml::Error) -> Self {
+        Self {
+            position: error
+                .location()
+                .map(|location| (location.line(), location.column())),
+            message: error.to_string(),
+        }
+    }
+
+    fn format(&self, file_name: &str) -> String {
+        match self.position {
+            Some((line, column)) => format!("{file_name}:{line}:{column}: {}", self.message),
+            None => format!("{file_name}: {}", self.message),
+        }
+    }
+}
+
+struct Checker<'a> {
+    locator: Locator<'a>,
+    issues: Vec<Issue>,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, position: Option<(usize, usize)>, message: String) {
+        self.issues.push(Issue { position, message });
+    }
+
+    fn check_document(&mut self, value: &Value) {
+        let Some(root) = value.as_mapping() else {
+            if !value.is_null() {
+                self.report(
+                    Some((1, 1)),
+                    "expected a mapping with plugins, themes and environments".to_string(),
+                );
+            }
+            return;
+        };
+
+        // Names are unique per environment, across plugins and themes
+        let mut default_names = HashSet::new();
+        for (key, value) in root {
+            match key.as_str() {
+                Some("plugins" | "themes") => self.check_list(value, &mut default_names),
+                Some("environments") => self.check_environments(value),
+                Some("clone") => {}
+                _ => {
+                    self.check_unknown_key(key, &["plugins", "themes", "environments", "clone"], 0)
+                }
+            }
+        }
+    }
+
+    fn check_environments(&mut self, value: &Value) {
+        let Some(environments) = value.as_mapping() else {
+            if !value.is_null() {
+                let position = self.locator.key(0, "environments");
+                self.report(position, "environments must be a mapping".to_string());
+            }
+            return;
+        };
+
+        let environments_line = self
+            .locator
+            .key(0, "environments")
+            .map_or(0, |(line, _)| line);
+        for (env, value) in environments {
+            if env.as_str() == Some(DEFAULT_ENV) {
+                let position = self.locator.key(environments_line, DEFAULT_ENV);
+                self.report(
+                    position,
+                    "the `default` environment is the top-level plugins and themes, list them there"
+                        .to_string(),
+                );
+                continue;
+            }
+
+            let mut names = HashSet::new();
+            let Some(lists) = value.as_mapping() else {
+                continue;
+            };
+            for (key, value) in lists {
+                match key.as_str() {
+                    Some("plugins" | "themes") => self.check_list(value, &mut names),
+                    _ => {
+                        let near = env.as_str().and_then(|env| self.locator.key(0, env));
+                        let near_line = near.map_or(0, |(line, _)| line);
+                        self.check_unknown_key(key, &["plugins", "themes"], near_line)
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_list(&mut self, value: &Value, names: &mut HashSet<String>) {
+        match value {
+            Value::Sequence(extensions) => {
+                for extension in extensions {
+                    self.check_extension(extension, names);
+                }
+            }
+            // An empty list may be written as a bare key
+            Value::Null => {}
+            _ => self.report(None, "plugins and themes must be lists".to_string()),
+        }
+    }
+
+    fn check_extension(&mut self, value: &Value, names: &mut HashSet<String>) {
+        let Some(mapping) = value.as_mapping() else {
+            self.report(None, "each extension must be a mapping".to_string());
+            return;
+        };
+
+        let Some(name) = mapping.get("name").and_then(Value::as_str) else {
+            self.report(None, "extension without a name".to_string());
+            return;
+        };
+        let position = self.locator.name(name);
+        let line = position.map_or(0, |(line, _)| line);
+        let issue_count = self.issues.len();
+
+        if !is_valid_name(name) {
+            self.report(
+                position,
+                format!("invalid name `{name}`, use letters, digits, `_`, `-` and `.`"),
+            );
+        }
+        if !names.insert(name.to_string()) {
+            self.report(position, format!("duplicate extension name `{name}`"));
+        }
+
+        let mut sources = Vec::new();
+        let mut unknown_keys = false;
+        for (key, value) in mapping {
+            match key.as_str() {
+                Some("name") => {}
+                Some("hooks") => self.check_keys(value, &HOOK_KEYS, line),
+                Some(source) if SOURCE_KEYS.contains(&source) => {
+                    sources.push(source);
+                    self.check_keys(value, &source_option_keys(source), line);
+                }
+                _ => {
+                    unknown_keys = true;
+                    let mut expected = vec!["name", "hooks"];
+                    expected.extend(SOURCE_KEYS);
+                    self.check_unknown_key(key, &expected, line);
+                }
+            }
+        }
+
+        match sources.as_slice() {
+            // A misspelled source key is already reported
+            [] if unknown_keys => {}
+            [] => self.report(
+                position,
+                format!(
+                    "`{name}` has no source, expected one of {}",
+                    SOURCE_KEYS.join(", ")
+                ),
+            ),
+            [_] => {}
+            _ => self.report(
+                position,
+                format!("`{name}` has more than one source: {}", sources.join(", ")),
+            ),
+        }
+
+        // Option combinations are only checked once the keys are known to be right
+        if self.issues.len() == issue_count {
+            match serde_yaml::from_value::<Extension>(value.clone()) {
+                Ok(extension) => {
+                    if let Err(e) = extension.source.validate() {
+                        let message = match e {
+                            RexerError::InvalidConfig(message) => message,
+                            e => e.to_string(),
+                        };
+                        self.report(position, format!("`{name}`: {message}"));
+                    }
+                }
+                Err(e) => self.report(position, format!("`{name}`: {e}")),
+            }
+        }
+    }
+
+    fn check_keys(&mut self, value: &Value, expected: &[&str], near_line: usize) {
+        let Some(mapping) = value.as_mapping() else {
+            return;
+        };
+        for key in mapping.keys() {
+            if !key.as_str().is_some_and(|key| expected.contains(&key)) {
+                self.check_unknown_key(key, expected, near_line);
+            }
+        }
+    }
+
+    fn check_unknown_key(&mut self, key: &Value, expected: &[&str], near_line: usize) {
+        let key = match key.as_str() {
+            Some(key) => key.to_string(),
+            None => format!("{key:?}"),
+        };
+
+        let mut message = format!("unknown key `{key}`");
+        if let Some(suggestion) = suggest(&key, expected) {
+            message.push_str(&format!(", did you mean `{suggestion}`?"));
+        } else {
+            message.push_str(&format!(", expected one of {}", expected.join(", ")));
+        }
+
+        let position = self.locator.key(near_line, &key);
+        self.report(position, message);
+    }
+}
+
+/// Finds the lines of keys in the raw text, which the parsed values don't keep
+struct Locator<'a> {
+    lines: Vec<&'a str>,
+    /// Line of the last occurrence found for each name, so repeated names resolve in order
+    found_names: HashMap<String, usize>,
+}
+
+impl<'a> Locator<'a> {
+    fn new(content: &'a str) -> Self {
+        Self {
+            lines: content.lines().collect(),
+            found_names: HashMap::new(),
+        }
+    }
+
+    /// Position of the next `name:` entry with the given value
+    fn name(&mut self, name: &str) -> Option<(usize, usize)> {
+        let start = self.found_names.get(name).copied().unwrap_or(0);
+        let index = (start..self.lines.len()).find(|&index| {
+            key_value(self.lines[index], "name").is_some_and(|value| unquote(value) == name)
+        })?;
+
+        self.found_names.insert(name.to_string(), index + 1);
+        let column = key_column(self.lines[index], "name").unwrap_or(1);
+        Some((index + 1, column))
+    }
+
+    /// Position of the `key:` closest to the given 1-based line, also inside flow mappings
+    fn key(&self, near_line: usize, key: &str) -> Option<(usize, usize)> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| Some((index + 1, key_column(line, key)?)))
+            .min_by_key(|(line, _)| line.abs_diff(near_line))
+    }
+}
+
+/// Value after `key:` when the line holds that key, possibly as a list item
+fn key_value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let trimmed = line.trim_start();
+    let trimmed = trimmed.strip_prefix("- ").unwrap_or(trimmed).trim_start();
+    trimmed.strip_prefix(key)?.strip_prefix(':').map(str::trim)
+}
+
+/// 1-based column of `key:` in the line, ignoring longer keys ending with the same text
+fn key_column(line: &str, key: &str) -> Option<usize> {
+    line.match_indices(key)
+        .find(|(index, _)| {
+            let before = line[..*index].chars().next_back();
+            let after = &line[index + key.len()..];
+            after.starts_with(':')
+                && !before.is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '-'))
+        })
+        .map(|(index, _)| line[..index].chars().count() + 1)
+}
+
+fn unquote(value: &str) -> &str {
+    let value = value.split(" #").next().unwrap_or(value).trim();
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .or_else(|| {
+            value
+                .strip_prefix('\'')
+                .and_then(|value| value.strip_suffix('\''))
+        })
+        .unwrap_or(value)
+}
+
+/// Extension names are used as directory names under plugins/ and themes/
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+fn source_option_keys(source: &str) -> Vec<&'static str> {
+    let mut keys = match source {
+        "git" => vec!["url"],
+        "github" => vec!["repo"],
+        "gitlab" | "bitbucket" | "gitea" => vec!["repo", "host"],
+        "path" => return vec!["path", "link"],
+        "archive" => return vec!["url", "sha256", "strip_components"],
+        _ => Vec::new(),
+    };
+    keys.extend(GIT_OPTION_KEYS);
+    keys
+}
+
+/// The expected key closest to a misspelled one
+fn suggest<'a>(key: &str, expected: &[&'a str]) -> Option<&'a str> {
+    expected
+        .iter()
+        .map(|candidate| (edit_distance(key, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
@@ -0,0 +1,70 @@
+use crate::error::{Result, RexerError};
+use semver::{Version, VersionReq};
+
+/// A requirement on the version tags of a repository, such as `~> 1.2` or `>=2.0, <3`
+pub struct VersionConstraint {
+    req: VersionReq,
+}
+
+impl VersionConstraint {
+    pub fn parse(constraint: &str) -> Result<Self> {
+        let comparators = constraint
+            .split(',')
+            .map(|comparator| expand_pessimistic(comparator.trim()))
+            .collect::<Result<Vec<_>>>()?
+            .join(", ");
+
+        let req = VersionReq::parse(&comparators).map_err(|e| {
+            RexerError::InvalidConfig(format!("Invalid version constraint '{constraint}': {e}"))
+        })?;
+
+        Ok(Self { req })
+    }
+
+    /// The tag with the highest version matching the constraint. Tags may have a
+    /// `v` prefix and omit the minor or patch number.
+    pub fn highest_match<'a>(&self, tags: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+        tags.into_iter()
+            .filter_map(|tag| tag_version(tag).map(|version| (version, tag)))
+            .filter(|(version, _)| self.req.matches(version))
+            .max_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, tag)| tag)
+    }
+}
+
+/// Expand the RubyGems style `~> X.Y` operator: `~> 1.2` allows `>=1.2.0, <2.0.0`
+/// and `~> 1.2.3` allows `>=1.2.3, <1.3.0`
+fn expand_pessimistic(comparator: &str) -> Result<String> {
+    let Some(version) = comparator.strip_prefix("~>") else {
+        return Ok(comparator.to_string());
+    };
+
+    let invalid =
+        || RexerError::InvalidConfig(format!("Invalid version constraint '{comparator}'"));
+    let parts = version
+        .trim()
+        .split('.')
+        .map(|part| part.parse::<u64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let (lower, upper) = match parts.as_slice() {
+        [major] => (format!("{major}.0.0"), format!("{}.0.0", major + 1)),
+        [major, minor] => (format!("{major}.{minor}.0"), format!("{}.0.0", major + 1)),
+        [major, minor, patch] => (
+            format!("{major}.{minor}.{patch}"),
+            format!("{major}.{}.0", minor + 1),
+        ),
+        _ => return Err(invalid()),
+    };
+
+    Ok(format!(">={lower}, <{upper}"))
+}
+
+fn tag_version(tag: &str) -> Option<Version> {
+    let version = tag.strip_prefix(['v', 'V']).unwrap_or(tag);
+
+    Version::parse(version)
+        .or_else(|_| Version::parse(&format!("{version}.0")))
+        .or_else(|_| Version::parse(&format!("{version}.0.0")))
+        .ok()
+}
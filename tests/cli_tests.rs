@@ -1,8 +1,98 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command as StdCommand;
 use tempfile::TempDir;
 
+/// Run a git command for test fixtures and return its trimmed stdout
+fn git(args: &[&str], dir: &Path) -> String {
+    let output = StdCommand::new("git")
+        .args([
+            "-c",
+            "user.name=rexer",
+            "-c",
+            "user.email=rexer@example.com",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?} failed");
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Create a local git repository containing the given files in a single commit
+fn create_git_repo(parent: &Path, name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let repo = parent.join(name);
+    fs::create_dir_all(&repo).unwrap();
+    git(&["init", "-q", "-b", "main"], &repo);
+    for (path, content) in files {
+        let file = repo.join(path);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, content).unwrap();
+    }
+    git(&["add", "-A"], &repo);
+    git(&["commit", "-q", "-m", "initial"], &repo);
+    repo
+}
+
+/// Write a .tar.gz archive containing the given files and return its SHA-256 digest
+fn create_tar_gz(path: &Path, files: &[(&str, &str)]) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+        Vec::new(),
+        flate2::Compression::default(),
+    ));
+    for (name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, name, content.as_bytes())
+            .unwrap();
+    }
+    let bytes = builder.into_inner().unwrap().finish().unwrap();
+    fs::write(path, &bytes).unwrap();
+
+    format!("{:x}", Sha256::digest(&bytes))
+}
+
+/// Create a script usable as REXER_COMMAND_PREFIX that records the commands
+/// instead of running them. Returns the script and the log file paths.
+#[cfg(unix)]
+fn create_command_recorder(dir: &Path) -> (PathBuf, PathBuf) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let log = dir.join("commands.log");
+    let script = dir.join("record");
+    fs::write(
+        &script,
+        format!("#!/bin/sh\necho \"$@\" >> \"{}\"\n", log.display()),
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    (script, log)
+}
+
+/// Count the staging directories left in the Redmine root
+fn staging_dirs(dir: &Path) -> usize {
+    fs::read_dir(dir)
+        .unwrap()
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with(".rexer-staging")
+        })
+        .count()
+}
+
 /// Basic CLI command tests
 #[test]
 fn test_version_command() {
@@ -101,6 +191,7 @@ fn test_install_command_no_config() {
 #[test]
 fn test_install_with_valid_config() {
     let temp_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
 
     // Create a test config with a simple GitHub repository
     let config_content = r#"plugins:
@@ -122,6 +213,7 @@ themes: []
     let mut cmd = Command::cargo_bin("rex").unwrap();
     cmd.arg("install")
         .current_dir(&temp_dir)
+        .env("REXER_CACHE_DIR", cache_dir.path())
         .timeout(std::time::Duration::from_secs(60))
         .assert()
         .success()
@@ -140,6 +232,7 @@ themes: []
 #[test]
 fn test_install_with_git_source() {
     let temp_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
 
     // Create a test config with a Git repository
     let config_content = r#"plugins: []
@@ -161,6 +254,7 @@ themes:
     let mut cmd = Command::cargo_bin("rex").unwrap();
     cmd.arg("install")
         .current_dir(&temp_dir)
+        .env("REXER_CACHE_DIR", cache_dir.path())
         .timeout(std::time::Duration::from_secs(60))
         .assert()
         .success()
@@ -193,6 +287,7 @@ fn test_install_invalid_config_format() {
 #[test]
 fn test_state_with_installed_extensions() {
     let temp_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
 
     // First install some extensions
     let config_content = r#"plugins:
@@ -215,6 +310,7 @@ themes: []
     let mut cmd = Command::cargo_bin("rex").unwrap();
     cmd.arg("install")
         .current_dir(&temp_dir)
+        .env("REXER_CACHE_DIR", cache_dir.path())
         .timeout(std::time::Duration::from_secs(60))
         .assert()
         .success();
@@ -223,6 +319,7 @@ themes: []
     let mut cmd = Command::cargo_bin("rex").unwrap();
     cmd.arg("state")
         .current_dir(&temp_dir)
+        .env("REXER_CACHE_DIR", cache_dir.path())
         .assert()
         .success()
         .stdout(predicate::str::contains("Rexer:"))
@@ -246,6 +343,7 @@ fn test_uninstall_no_lock_file() {
 #[test]
 fn test_uninstall_with_installed_extensions() {
     let temp_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
 
     // First install some extensions
     let config_content = r#"plugins:
@@ -268,6 +366,7 @@ themes: []
     let mut cmd = Command::cargo_bin("rex").unwrap();
     cmd.arg("install")
         .current_dir(&temp_dir)
+        .env("REXER_CACHE_DIR", cache_dir.path())
         .timeout(std::time::Duration::from_secs(60))
         .assert()
         .success();
@@ -280,6 +379,7 @@ themes: []
     let mut cmd = Command::cargo_bin("rex").unwrap();
     cmd.arg("uninstall")
         .current_dir(&temp_dir)
+        .env("REXER_CACHE_DIR", cache_dir.path())
         .assert()
         .success()
         .stdout(predicate::str::contains("Uninstalling test_plugin"))
@@ -309,6 +409,7 @@ fn test_update_no_lock_file() {
 #[test]
 fn test_update_specific_extension() {
     let temp_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
 
     // First install some extensions
     let config_content = r#"plugins:
@@ -331,6 +432,7 @@ themes: []
     let mut cmd = Command::cargo_bin("rex").unwrap();
     cmd.arg("install")
         .current_dir(&temp_dir)
+        .env("REXER_CACHE_DIR", cache_dir.path())
         .timeout(std::time::Duration::from_secs(60))
         .assert()
         .success();
@@ -340,6 +442,7 @@ themes: []
     cmd.arg("update")
         .arg("test_plugin")
         .current_dir(&temp_dir)
+        .env("REXER_CACHE_DIR", cache_dir.path())
         .timeout(std::time::Duration::from_secs(60))
         .assert()
         .success()
@@ -349,6 +452,7 @@ themes: []
 #[test]
 fn test_update_all_extensions() {
     let temp_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
 
     // First install some extensions
     let config_content = r#"plugins:
@@ -371,6 +475,7 @@ themes: []
     let mut cmd = Command::cargo_bin("rex").unwrap();
     cmd.arg("install")
         .current_dir(&temp_dir)
+        .env("REXER_CACHE_DIR", cache_dir.path())
         .timeout(std::time::Duration::from_secs(60))
         .assert()
         .success();
@@ -382,6 +487,7 @@ themes: []
     let mut cmd = Command::cargo_bin("rex").unwrap();
     cmd.arg("update")
         .current_dir(&temp_dir)
+        .env("REXER_CACHE_DIR", cache_dir.path())
         .timeout(std::time::Duration::from_secs(60))
         .assert()
         .success()
@@ -501,3 +607,2497 @@ fn test_quiet_mode() {
         .assert()
         .success();
 }
+
+/// Environment tests
+#[test]
+fn test_switch_environment() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let plugin_repo = create_git_repo(repos_dir.path(), "plugin", &[("init.rb", "")]);
+    let theme_repo = create_git_repo(repos_dir.path(), "theme", &[("README", "theme")]);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: test_plugin
+    git:
+      url: "{}"
+
+environments:
+  staging:
+    themes:
+      - name: test_theme
+        git:
+          url: "{}"
+"#,
+        plugin_repo.display(),
+        theme_repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install").current_dir(&temp_dir).assert().success();
+    assert!(temp_dir.path().join("plugins/test_plugin").exists());
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("switch")
+        .arg("staging")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Installing test_theme"))
+        .stdout(predicate::str::contains("Uninstalling test_plugin"))
+        .stdout(predicate::str::contains("Switched to staging"));
+
+    assert!(!temp_dir.path().join("plugins/test_plugin").exists());
+    assert!(temp_dir.path().join("themes/test_theme").exists());
+
+    let lock_content = fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap();
+    let lock_data: serde_json::Value = serde_json::from_str(&lock_content).unwrap();
+    assert_eq!(lock_data["env"], "staging");
+
+    // Install keeps using the environment recorded in the lock file
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Extensions are up to date"));
+}
+
+#[test]
+fn test_switch_unknown_environment() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join(".extensions.yml"), "plugins: []\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("switch")
+        .arg("production")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("EnvironmentNotFound"));
+}
+
+#[test]
+fn test_envs_command() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_content = r#"plugins:
+  - name: test_plugin
+    github:
+      repo: "octocat/Hello-World"
+
+environments:
+  staging:
+    themes:
+      - name: test_theme
+        git:
+          url: "https://github.com/octocat/Hello-World.git"
+          branch: "master"
+"#;
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("envs")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("default"))
+        .stdout(predicate::str::contains(
+            " * test_plugin [plugin] (github: octocat/Hello-World)",
+        ))
+        .stdout(predicate::str::contains("staging"))
+        .stdout(predicate::str::contains(
+            " * test_theme [theme] (git: https://github.com/octocat/Hello-World.git at branch master)",
+        ));
+}
+
+/// Hook tests
+#[test]
+fn test_install_and_uninstall_hooks() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let plugin_repo = create_git_repo(repos_dir.path(), "plugin", &[("init.rb", "")]);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: test_plugin
+    git:
+      url: "{}"
+    hooks:
+      installed: "touch installed.txt"
+      uninstalled: "touch uninstalled.txt"
+"#,
+        plugin_repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Running installed hook for test_plugin",
+        ));
+    assert!(temp_dir.path().join("installed.txt").exists());
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("uninstall")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Running uninstalled hook for test_plugin",
+        ));
+    assert!(temp_dir.path().join("uninstalled.txt").exists());
+}
+
+#[test]
+fn test_failed_hook_is_reported() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let plugin_repo = create_git_repo(repos_dir.path(), "plugin", &[("init.rb", "")]);
+    let theme_repo = create_git_repo(repos_dir.path(), "theme", &[("README", "theme")]);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: test_plugin
+    git:
+      url: "{}"
+    hooks:
+      installed: "exit 1"
+
+themes:
+  - name: test_theme
+    git:
+      url: "{}"
+"#,
+        plugin_repo.display(),
+        theme_repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    // The failing hook does not prevent other extensions from being installed
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("test_plugin (installed)"));
+
+    assert!(temp_dir.path().join("themes/test_theme").exists());
+    assert!(temp_dir.path().join(".extensions.lock").exists());
+}
+
+/// Local path source tests
+#[test]
+fn test_install_path_source_as_symlink() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = TempDir::new().unwrap();
+    fs::write(source_dir.path().join("init.rb"), "").unwrap();
+
+    let config_content = format!(
+        r#"plugins:
+  - name: local_plugin
+    path:
+      path: "{}"
+"#,
+        source_dir.path().display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Installed 1 extensions"));
+
+    let plugin_path = temp_dir.path().join("plugins/local_plugin");
+    assert!(fs::symlink_metadata(&plugin_path)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+
+    let lock_content = fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap();
+    let lock_data: serde_json::Value = serde_json::from_str(&lock_content).unwrap();
+    let locked = &lock_data["extensions"][0];
+    assert_eq!(
+        locked["resolved_path"],
+        source_dir
+            .path()
+            .canonicalize()
+            .unwrap()
+            .display()
+            .to_string()
+    );
+    assert!(locked["digest"].is_string());
+    assert!(locked["commit_hash"].is_null());
+
+    // Uninstall removes only the symlink
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("uninstall")
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    assert!(fs::symlink_metadata(&plugin_path).is_err());
+    assert!(source_dir.path().join("init.rb").exists());
+}
+
+#[test]
+fn test_update_path_source_resyncs_copy() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = temp_dir.path().join("src/local_theme");
+    fs::create_dir_all(&source_dir).unwrap();
+    fs::write(source_dir.join("application.css"), "body {}").unwrap();
+
+    let config_content = r#"themes:
+  - name: local_theme
+    path:
+      path: "src/local_theme"
+      link: copy
+"#;
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install").current_dir(&temp_dir).assert().success();
+
+    let installed_css = temp_dir.path().join("themes/local_theme/application.css");
+    assert_eq!(fs::read_to_string(&installed_css).unwrap(), "body {}");
+
+    // Nothing changed yet
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("update")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("already up to date"));
+
+    fs::write(source_dir.join("application.css"), "body { color: red }").unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("update")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Updated 1 extension(s)"));
+    assert_eq!(
+        fs::read_to_string(&installed_css).unwrap(),
+        "body { color: red }"
+    );
+}
+
+/// Archive source tests
+#[test]
+fn test_install_archive_source() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive_dir = TempDir::new().unwrap();
+    let archive_path = archive_dir.path().join("redmine_archived-1.0.0.tar.gz");
+    let sha256 = create_tar_gz(
+        &archive_path,
+        &[
+            ("redmine_archived-1.0.0/init.rb", "Redmine::Plugin.register"),
+            ("redmine_archived-1.0.0/app/views/index.erb", ""),
+        ],
+    );
+
+    let config_content = format!(
+        r#"plugins:
+  - name: redmine_archived
+    archive:
+      url: "file://{}"
+      sha256: "{sha256}"
+      strip_components: 1
+"#,
+        archive_path.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Installed 1 extensions"));
+
+    let plugin_path = temp_dir.path().join("plugins/redmine_archived");
+    assert!(plugin_path.join("init.rb").exists());
+    assert!(plugin_path.join("app/views/index.erb").exists());
+
+    let lock_content = fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap();
+    let lock_data: serde_json::Value = serde_json::from_str(&lock_content).unwrap();
+    assert_eq!(
+        lock_data["extensions"][0]["source"]["archive"]["sha256"],
+        sha256
+    );
+    assert!(lock_data["extensions"][0]["digest"].is_string());
+    assert_ne!(lock_data["extensions"][0]["digest"], sha256);
+}
+
+#[test]
+fn test_install_archive_checksum_mismatch() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive_dir = TempDir::new().unwrap();
+    let archive_path = archive_dir.path().join("redmine_archived.tar.gz");
+    create_tar_gz(&archive_path, &[("init.rb", "")]);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: redmine_archived
+    archive:
+      url: "file://{}"
+      sha256: "0000000000000000000000000000000000000000000000000000000000000000"
+"#,
+        archive_path.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Checksum mismatch"));
+
+    assert!(!temp_dir.path().join("plugins/redmine_archived").exists());
+    assert!(!temp_dir.path().join(".extensions.lock").exists());
+}
+
+#[test]
+fn test_install_archive_rejects_symlink_escape() {
+    use sha2::{Digest, Sha256};
+
+    let temp_dir = TempDir::new().unwrap();
+    let archive_dir = TempDir::new().unwrap();
+    let outside_dir = TempDir::new().unwrap();
+    let archive_path = archive_dir.path().join("redmine_evil.tar.gz");
+
+    let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(
+        Vec::new(),
+        flate2::Compression::default(),
+    ));
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Symlink);
+    header.set_size(0);
+    header.set_mode(0o777);
+    builder
+        .append_link(&mut header, "link", outside_dir.path())
+        .unwrap();
+    let mut header = tar::Header::new_gnu();
+    header.set_size(4);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, "link/escaped.txt", "evil".as_bytes())
+        .unwrap();
+    let bytes = builder.into_inner().unwrap().finish().unwrap();
+    fs::write(&archive_path, &bytes).unwrap();
+    let sha256 = format!("{:x}", Sha256::digest(&bytes));
+
+    let config_content = format!(
+        r#"plugins:
+  - name: redmine_evil
+    archive:
+      url: "file://{}"
+      sha256: "{sha256}"
+"#,
+        archive_path.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Unsafe link in archive"));
+
+    assert!(!outside_dir.path().join("escaped.txt").exists());
+    assert!(!temp_dir.path().join("plugins/redmine_evil").exists());
+}
+
+/// Hosted Git shorthand tests
+#[test]
+fn test_install_gitlab_source_with_host() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
+    create_git_repo(
+        &repos_dir.path().join("group"),
+        "plugin.git",
+        &[("init.rb", "")],
+    );
+
+    let config_content = format!(
+        r#"plugins:
+  - name: gitlab_plugin
+    gitlab:
+      repo: "group/plugin"
+      host: "file://{}"
+      branch: "main"
+"#,
+        repos_dir.path().display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    assert!(temp_dir
+        .path()
+        .join("plugins/gitlab_plugin/init.rb")
+        .exists());
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("state")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "gitlab_plugin (gitlab: file://{}/group/plugin at branch main, installed: ",
+            repos_dir.path().display()
+        )));
+}
+
+#[test]
+fn test_hosted_sources_format() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_content = r#"plugins:
+  - name: bitbucket_plugin
+    bitbucket:
+      repo: "team/plugin"
+      tag: "v1.0.0"
+  - name: gitea_plugin
+    gitea:
+      repo: "owner/plugin"
+      host: "git.example.com"
+"#;
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("envs")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "bitbucket_plugin [plugin] (bitbucket: team/plugin at tag v1.0.0)",
+        ))
+        .stdout(predicate::str::contains(
+            "gitea_plugin [plugin] (gitea: git.example.com/owner/plugin)",
+        ));
+}
+
+/// Monorepo subdirectory tests
+#[test]
+fn test_install_subdirectories_of_monorepo() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let monorepo = create_git_repo(
+        repos_dir.path(),
+        "monorepo",
+        &[
+            ("plugins/plugin_a/init.rb", "a"),
+            ("plugins/plugin_b/init.rb", "b"),
+            ("README", "monorepo"),
+        ],
+    );
+    let head = git(&["rev-parse", "HEAD"], &monorepo);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: plugin_a
+    git:
+      url: "{0}"
+      path: "plugins/plugin_a"
+  - name: plugin_b
+    git:
+      url: "{0}"
+      path: "plugins/plugin_b"
+"#,
+        monorepo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install").current_dir(&temp_dir).assert().success();
+
+    let plugin_a = temp_dir.path().join("plugins/plugin_a");
+    assert_eq!(fs::read_to_string(plugin_a.join("init.rb")).unwrap(), "a");
+    assert!(!plugin_a.join(".git").exists());
+    assert!(!plugin_a.join("README").exists());
+    assert!(temp_dir.path().join("plugins/plugin_b/init.rb").exists());
+
+    // The repository is cloned once for both plugins
+    let repos = fs::read_dir(temp_dir.path().join(".rexer/repos")).unwrap();
+    assert_eq!(repos.count(), 1);
+
+    let lock_content = fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap();
+    let lock_data: serde_json::Value = serde_json::from_str(&lock_content).unwrap();
+    assert_eq!(lock_data["extensions"][0]["commit_hash"], head);
+    assert_eq!(
+        lock_data["extensions"][0]["source"]["git"]["path"],
+        "plugins/plugin_a"
+    );
+
+    // Update picks up new commits of the repository
+    fs::write(monorepo.join("plugins/plugin_a/init.rb"), "a2").unwrap();
+    git(&["commit", "-q", "-am", "update a"], &monorepo);
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("update")
+        .arg("plugin_a")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Updated 1 extension(s)"));
+    assert_eq!(fs::read_to_string(plugin_a.join("init.rb")).unwrap(), "a2");
+}
+
+/// Plugin data removal tests
+#[cfg(unix)]
+#[test]
+fn test_uninstall_rolls_back_plugin_migrations() {
+    let temp_dir = TempDir::new().unwrap();
+    let tools_dir = TempDir::new().unwrap();
+    let (recorder, log) = create_command_recorder(tools_dir.path());
+    let plugin_repo = create_git_repo(
+        tools_dir.path(),
+        "plugin",
+        &[("init.rb", ""), ("db/migrate/001_create_items.rb", "")],
+    );
+
+    let config_content = format!(
+        r#"plugins:
+  - name: test_plugin
+    git:
+      url: "{}"
+"#,
+        plugin_repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_COMMAND_PREFIX", &recorder)
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("uninstall")
+        .env("REXER_COMMAND_PREFIX", &recorder)
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+
+    let commands = fs::read_to_string(&log).unwrap();
+    assert!(commands.contains("bundle exec rake redmine:plugins:migrate NAME=test_plugin\n"));
+    assert!(
+        commands.contains("bundle exec rake redmine:plugins:migrate NAME=test_plugin VERSION=0\n")
+    );
+    assert!(!temp_dir.path().join("plugins/test_plugin").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_uninstall_keep_data() {
+    let temp_dir = TempDir::new().unwrap();
+    let tools_dir = TempDir::new().unwrap();
+    let (recorder, log) = create_command_recorder(tools_dir.path());
+    let plugin_repo = create_git_repo(
+        tools_dir.path(),
+        "plugin",
+        &[("init.rb", ""), ("db/migrate/001_create_items.rb", "")],
+    );
+
+    let config_content = format!(
+        r#"plugins:
+  - name: test_plugin
+    git:
+      url: "{}"
+"#,
+        plugin_repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_COMMAND_PREFIX", &recorder)
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("uninstall")
+        .arg("--keep-data")
+        .env("REXER_COMMAND_PREFIX", &recorder)
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+
+    let commands = fs::read_to_string(&log).unwrap();
+    assert!(!commands.contains("VERSION=0"));
+    assert!(!temp_dir.path().join("plugins/test_plugin").exists());
+
+    // Removing the plugin from the configuration can keep its data as well
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_COMMAND_PREFIX", &recorder)
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["remove", "test_plugin", "--install", "--keep-data"])
+        .env("REXER_COMMAND_PREFIX", &recorder)
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+
+    let commands = fs::read_to_string(&log).unwrap();
+    assert!(!commands.contains("VERSION=0"));
+    assert!(!temp_dir.path().join("plugins/test_plugin").exists());
+}
+
+/// Dry run tests
+#[test]
+fn test_install_dry_run() {
+    let temp_dir = TempDir::new().unwrap();
+    let source_dir = temp_dir.path().join("src/local_plugin");
+    fs::create_dir_all(source_dir.join("db/migrate")).unwrap();
+    fs::write(source_dir.join("Gemfile"), "").unwrap();
+    fs::write(source_dir.join("db/migrate/001_create_items.rb"), "").unwrap();
+
+    let config_content = r#"plugins:
+  - name: local_plugin
+    path:
+      path: "src/local_plugin"
+  - name: remote_plugin
+    github:
+      repo: "octocat/Hello-World"
+"#;
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .arg("--dry-run")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "+ local_plugin (path: src/local_plugin) [bundle install, migrations]",
+        ))
+        .stdout(predicate::str::contains("+ remote_plugin"))
+        .stdout(predicate::str::contains(
+            "Plan: 2 to add, 0 to change, 0 to remove",
+        ));
+
+    let output = Command::cargo_bin("rex")
+        .unwrap()
+        .args(["install", "--dry-run", "--json"])
+        .current_dir(&temp_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let plan: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(plan["env"], "default");
+    assert_eq!(plan["add"][0]["name"], "local_plugin");
+    assert_eq!(plan["add"][0]["bundle_install"], true);
+    assert_eq!(plan["add"][0]["migrate"], true);
+    assert!(plan["add"][1]["bundle_install"].is_null());
+
+    // Nothing was installed
+    assert!(!temp_dir.path().join("plugins").exists());
+    assert!(!temp_dir.path().join(".extensions.lock").exists());
+}
+
+#[test]
+fn test_install_dry_run_with_lock_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let plugin_repo = create_git_repo(repos_dir.path(), "plugin", &[("init.rb", "")]);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: test_plugin
+    git:
+      url: "{}"
+"#,
+        plugin_repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install").current_dir(&temp_dir).assert().success();
+    let lock_before = fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .arg("--dry-run")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "No changes for environment default",
+        ));
+
+    // A missing directory is restored from the lock file
+    let plugin_dir = temp_dir.path().join("plugins/test_plugin");
+    fs::rename(&plugin_dir, temp_dir.path().join("moved_plugin")).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .arg("--dry-run")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("+ test_plugin"))
+        .stdout(predicate::str::contains("restore from lock at"))
+        .stdout(predicate::str::contains(
+            "Plan: 0 to add, 0 to change, 0 to remove, 1 to restore",
+        ));
+
+    let output = Command::cargo_bin("rex")
+        .unwrap()
+        .args(["install", "--dry-run", "--json"])
+        .current_dir(&temp_dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let plan: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(plan["restore"][0]["name"], "test_plugin");
+    assert!(plan["restore"][0]["commit_hash"].is_string());
+
+    fs::rename(temp_dir.path().join("moved_plugin"), &plugin_dir).unwrap();
+    fs::write(temp_dir.path().join(".extensions.yml"), "plugins: []\n").unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .arg("--dry-run")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("- test_plugin"))
+        .stdout(predicate::str::contains(
+            "Plan: 0 to add, 0 to change, 1 to remove",
+        ));
+
+    assert!(temp_dir.path().join("plugins/test_plugin").exists());
+    let lock_after = fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap();
+    assert_eq!(lock_before, lock_after);
+}
+
+/// Atomic install tests
+#[test]
+fn test_failed_fresh_install_leaves_nothing_behind() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let plugin_repo = create_git_repo(repos_dir.path(), "plugin", &[("init.rb", "")]);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: good_plugin
+    git:
+      url: "{}"
+  - name: broken_plugin
+    git:
+      url: "{}"
+"#,
+        plugin_repo.display(),
+        repos_dir.path().join("missing").display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "restoring the previous installation",
+        ));
+
+    assert!(!temp_dir.path().join("plugins/good_plugin").exists());
+    assert!(!temp_dir.path().join(".extensions.lock").exists());
+    assert_eq!(staging_dirs(temp_dir.path()), 0);
+}
+
+#[test]
+fn test_failed_setup_restores_previous_installation() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let plugin_v1 = create_git_repo(repos_dir.path(), "plugin_v1", &[("init.rb", "v1")]);
+    let plugin_v2 = create_git_repo(
+        repos_dir.path(),
+        "plugin_v2",
+        &[("init.rb", "v2"), ("Gemfile", "")],
+    );
+    let theme_repo = create_git_repo(repos_dir.path(), "theme", &[("README", "theme")]);
+
+    let config_v1 = format!(
+        r#"plugins:
+  - name: test_plugin
+    git:
+      url: "{}"
+themes:
+  - name: test_theme
+    git:
+      url: "{}"
+"#,
+        plugin_v1.display(),
+        theme_repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_v1).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install").current_dir(&temp_dir).assert().success();
+    let lock_before = fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap();
+
+    // Switch the plugin to a version whose bundle install fails and remove the theme
+    let config_v2 = format!(
+        r#"plugins:
+  - name: test_plugin
+    git:
+      url: "{}"
+"#,
+        plugin_v2.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_v2).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_COMMAND_PREFIX", "false")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure();
+
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("plugins/test_plugin/init.rb")).unwrap(),
+        "v1"
+    );
+    assert!(temp_dir.path().join("themes/test_theme/README").exists());
+    let lock_after = fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap();
+    assert_eq!(lock_before, lock_after);
+    assert_eq!(staging_dirs(temp_dir.path()), 0);
+}
+
+/// Parallel fetch tests
+#[test]
+fn test_install_fetches_concurrently_in_config_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+
+    let names = ["plugin_a", "plugin_b", "plugin_c", "plugin_d"];
+    let mut config_content = String::from("plugins:\n");
+    for name in names {
+        let repo = create_git_repo(repos_dir.path(), name, &[("init.rb", name)]);
+        config_content.push_str(&format!(
+            "  - name: {name}\n    git:\n      url: \"{}\"\n",
+            repo.display()
+        ));
+    }
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--jobs", "3"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Installing plugin_c... done"))
+        .stdout(predicate::str::contains("Installed 4 extensions"));
+
+    for name in names {
+        let init = temp_dir.path().join("plugins").join(name).join("init.rb");
+        assert_eq!(fs::read_to_string(init).unwrap(), name);
+    }
+
+    // The lock file keeps the order of the configuration
+    let lock_content = fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap();
+    let lock_data: serde_json::Value = serde_json::from_str(&lock_content).unwrap();
+    let locked: Vec<_> = lock_data["extensions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|ext| ext["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(locked, names);
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["update", "-j", "2"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "All extensions are already up to date",
+        ));
+}
+
+/// Outdated tests
+#[test]
+fn test_outdated_reports_newer_commits_and_tags() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let branch_repo = create_git_repo(repos_dir.path(), "branch_plugin", &[("init.rb", "1")]);
+    let tag_repo = create_git_repo(repos_dir.path(), "tag_plugin", &[("init.rb", "1")]);
+    git(&["tag", "v1.0.0"], &tag_repo);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: branch_plugin
+    git:
+      url: "{}"
+      branch: "main"
+  - name: tag_plugin
+    git:
+      url: "{}"
+      tag: "v1.0.0"
+"#,
+        branch_repo.display(),
+        tag_repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install").current_dir(&temp_dir).assert().success();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("outdated")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("All extensions are up to date"));
+
+    // New upstream commits and tags are reported without touching the installation
+    let installed = git(&["rev-parse", "HEAD"], &branch_repo);
+    fs::write(branch_repo.join("init.rb"), "2").unwrap();
+    git(&["commit", "-q", "-am", "update"], &branch_repo);
+    let latest = git(&["rev-parse", "HEAD"], &branch_repo);
+    git(&["tag", "v1.10.0"], &tag_repo);
+    git(&["tag", "v1.9.0"], &tag_repo);
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("outdated")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "branch_plugin (branch main): {} -> {}",
+            &installed[..8],
+            &latest[..8]
+        )))
+        .stdout(predicate::str::contains("tag_plugin (tag v1.0.0): "))
+        .stdout(predicate::str::contains("newest tag: v1.10.0"))
+        .stdout(predicate::str::contains("2 extension(s) outdated"));
+
+    let init = temp_dir.path().join("plugins/branch_plugin/init.rb");
+    assert_eq!(fs::read_to_string(init).unwrap(), "1");
+
+    // An unreachable remote fails its own line, the others are still reported
+    fs::rename(&tag_repo, repos_dir.path().join("moved")).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("outdated")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("tag_plugin: failed:"))
+        .stdout(predicate::str::contains("1 extension(s) outdated"))
+        .stderr(predicate::str::contains(
+            "1 extension(s) could not be checked",
+        ));
+}
+
+/// Version constraint tests
+#[test]
+fn test_install_and_update_within_version_constraint() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let repo = create_git_repo(repos_dir.path(), "versioned", &[("VERSION", "1.2.0")]);
+    git(&["tag", "v1.2.0"], &repo);
+
+    let release = |version: &str| {
+        fs::write(repo.join("VERSION"), version).unwrap();
+        git(&["commit", "-q", "-am", version], &repo);
+        git(&["tag", &format!("v{version}")], &repo);
+        git(&["rev-parse", "HEAD"], &repo)
+    };
+    let v1_3 = release("1.3.0");
+    release("2.0.0");
+
+    let config_content = format!(
+        r#"plugins:
+  - name: versioned
+    git:
+      url: "{}"
+      version: "~> 1.2"
+"#,
+        repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install").current_dir(&temp_dir).assert().success();
+
+    let version_file = temp_dir.path().join("plugins/versioned/VERSION");
+    assert_eq!(fs::read_to_string(&version_file).unwrap(), "1.3.0");
+
+    let lock_path = temp_dir.path().join(".extensions.lock");
+    let lock_data: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&lock_path).unwrap()).unwrap();
+    assert_eq!(lock_data["extensions"][0]["resolved_tag"], "v1.3.0");
+    assert_eq!(lock_data["extensions"][0]["commit_hash"], v1_3);
+    assert_eq!(
+        lock_data["extensions"][0]["source"]["git"]["version"],
+        "~> 1.2"
+    );
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("state")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("at version ~> 1.2"))
+        .stdout(predicate::str::contains("tag: v1.3.0"));
+
+    // Update moves to the newest release allowed by the constraint
+    let v1_10 = release("1.10.0");
+    release("2.1.0");
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("update")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Updated 1 extension(s)"));
+
+    assert_eq!(fs::read_to_string(&version_file).unwrap(), "1.10.0");
+    let lock_data: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&lock_path).unwrap()).unwrap();
+    assert_eq!(lock_data["extensions"][0]["resolved_tag"], "v1.10.0");
+    assert_eq!(lock_data["extensions"][0]["commit_hash"], v1_10);
+}
+
+#[test]
+fn test_install_version_constraint_without_matching_tag() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let repo = create_git_repo(repos_dir.path(), "versioned", &[("init.rb", "")]);
+    git(&["tag", "v1.0.0"], &repo);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: versioned
+    git:
+      url: "{}"
+      version: ">=2.0, <3"
+"#,
+        repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("matches version >=2.0, <3"));
+    assert!(!temp_dir.path().join("plugins/versioned").exists());
+}
+
+/// Frozen install tests
+#[test]
+fn test_frozen_install_uses_locked_commit() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let repo = create_git_repo(repos_dir.path(), "frozen_plugin", &[("init.rb", "locked")]);
+    let locked_commit = git(&["rev-parse", "HEAD"], &repo);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: frozen_plugin
+    git:
+      url: "{}"
+      branch: "main"
+"#,
+        repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), &config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--frozen"])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--frozen requires .extensions.lock",
+        ));
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install").current_dir(&temp_dir).assert().success();
+
+    // A new upstream commit on the branch is ignored
+    fs::write(repo.join("init.rb"), "newer").unwrap();
+    git(&["commit", "-q", "-am", "newer"], &repo);
+    let plugin_dir = temp_dir.path().join("plugins/frozen_plugin");
+    fs::remove_dir_all(&plugin_dir).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--frozen"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Installed 1 extensions from the lock file",
+        ));
+    assert_eq!(
+        fs::read_to_string(plugin_dir.join("init.rb")).unwrap(),
+        "locked"
+    );
+    assert_eq!(git(&["rev-parse", "HEAD"], &plugin_dir), locked_commit);
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--frozen"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Extensions are up to date"));
+
+    // Configuration changes are rejected instead of resolved
+    let changed_config = config_content.replace("branch: \"main\"", "tag: \"v1.0.0\"");
+    fs::write(temp_dir.path().join(".extensions.yml"), changed_config).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--frozen"])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "does not match .extensions.yml (changed: frozen_plugin)",
+        ));
+}
+
+#[test]
+fn test_frozen_install_keeps_archive_extension() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive_dir = TempDir::new().unwrap();
+    let archive_path = archive_dir.path().join("redmine_archived.tar.gz");
+    let sha256 = create_tar_gz(&archive_path, &[("init.rb", "")]);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: redmine_archived
+    archive:
+      url: "file://{}"
+      sha256: "{sha256}"
+"#,
+        archive_path.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install").current_dir(&temp_dir).assert().success();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--frozen"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Extensions are up to date"));
+}
+
+/// Lock file restore tests
+#[test]
+fn test_install_restores_missing_extension_at_locked_commit() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
+    let repo = create_git_repo(repos_dir.path(), "locked_plugin", &[("init.rb", "locked")]);
+    let locked_commit = git(&["rev-parse", "HEAD"], &repo);
+
+    // A file:// URL only transfers reachable commits, like a remote server
+    let config_content = format!(
+        r#"plugins:
+  - name: locked_plugin
+    git:
+      url: "file://{}"
+      branch: "main"
+"#,
+        repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+
+    fs::write(repo.join("init.rb"), "newer").unwrap();
+    git(&["commit", "-q", "-am", "newer"], &repo);
+    let plugin_dir = temp_dir.path().join("plugins/locked_plugin");
+    fs::remove_dir_all(&plugin_dir).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Restoring locked_plugin at {}",
+            &locked_commit[..8]
+        )));
+    assert_eq!(git(&["rev-parse", "HEAD"], &plugin_dir), locked_commit);
+
+    // The locked commit disappears from the remote after a force push
+    git(&["reset", "-q", "--hard", &locked_commit], &repo);
+    fs::write(repo.join("init.rb"), "rewritten").unwrap();
+    git(&["commit", "-q", "--amend", "-am", "rewritten"], &repo);
+    git(&["reflog", "expire", "--expire=now", "--all"], &repo);
+    git(&["gc", "-q", "--prune=now"], &repo);
+    let rewritten_commit = git(&["rev-parse", "HEAD"], &repo);
+    fs::remove_dir_all(&plugin_dir).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "drift: the locked commit is not available upstream",
+        ));
+    assert_eq!(
+        fs::read_to_string(plugin_dir.join("init.rb")).unwrap(),
+        "rewritten"
+    );
+
+    let lock_content = fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap();
+    let lock_data: serde_json::Value = serde_json::from_str(&lock_content).unwrap();
+    assert_eq!(lock_data["extensions"][0]["commit_hash"], rewritten_commit);
+
+    // An unreachable remote is an error, not a drift
+    fs::rename(&repo, repos_dir.path().join("moved")).unwrap();
+    fs::remove_dir_all(&plugin_dir).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("drift").not());
+    assert!(!plugin_dir.exists());
+}
+
+/// Local modification tests
+#[test]
+fn test_status_and_update_with_local_modifications() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let repo = create_git_repo(
+        repos_dir.path(),
+        "patched_plugin",
+        &[("init.rb", "original"), ("lib/patch.rb", "original")],
+    );
+
+    let config_content = format!(
+        r#"plugins:
+  - name: patched_plugin
+    git:
+      url: "{}"
+"#,
+        repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install").current_dir(&temp_dir).assert().success();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("status")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("patched_plugin [plugin]: ok"))
+        .stdout(predicate::str::contains(
+            "All extensions match .extensions.lock",
+        ));
+
+    // Hot-patch a file in the installed plugin
+    let plugin_dir = temp_dir.path().join("plugins/patched_plugin");
+    fs::write(plugin_dir.join("lib/patch.rb"), "hot patch").unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("verify")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("modified: lib/patch.rb"))
+        .stderr(predicate::str::contains("1 extension(s) differ"));
+
+    fs::write(repo.join("init.rb"), "updated").unwrap();
+    git(&["commit", "-q", "-am", "update"], &repo);
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("update")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "patched_plugin has local changes (lib/patch.rb), use --force",
+        ));
+    assert_eq!(
+        fs::read_to_string(plugin_dir.join("lib/patch.rb")).unwrap(),
+        "hot patch"
+    );
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["reinstall", "patched_plugin"])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("use --force"));
+
+    // Added files count as local changes too
+    fs::write(plugin_dir.join("lib/override.rb"), "added").unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("status")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains(
+            "modified: lib/patch.rb, lib/override.rb",
+        ));
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["update", "--force"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Updated 1 extension(s)"));
+    assert_eq!(
+        fs::read_to_string(plugin_dir.join("lib/patch.rb")).unwrap(),
+        "original"
+    );
+    assert!(!plugin_dir.join("lib/override.rb").exists());
+    assert_eq!(
+        fs::read_to_string(plugin_dir.join("init.rb")).unwrap(),
+        "updated"
+    );
+
+    // A checkout moved away from the locked commit is reported as well
+    git(&["checkout", "-q", "HEAD~1"], &plugin_dir);
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("status")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("locked"));
+
+    fs::remove_dir_all(&plugin_dir).unwrap();
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("status")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("patched_plugin [plugin]: missing"));
+}
+
+#[test]
+fn test_update_leaves_symlinked_working_copy_alone() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let working_copy = create_git_repo(repos_dir.path(), "dev_plugin", &[("init.rb", "committed")]);
+    fs::write(working_copy.join("init.rb"), "work in progress").unwrap();
+
+    let config_content = format!(
+        r#"plugins:
+  - name: dev_plugin
+    path:
+      path: "{}"
+"#,
+        working_copy.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install").current_dir(&temp_dir).assert().success();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("status")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("dev_plugin [plugin]: ok"));
+
+    for args in [&["update"][..], &["update", "--force"]] {
+        let mut cmd = Command::cargo_bin("rex").unwrap();
+        cmd.args(args).current_dir(&temp_dir).assert().success();
+    }
+    assert_eq!(
+        fs::read_to_string(working_copy.join("init.rb")).unwrap(),
+        "work in progress"
+    );
+}
+
+#[test]
+fn test_status_and_reinstall_archive_source() {
+    let temp_dir = TempDir::new().unwrap();
+    let archive_dir = TempDir::new().unwrap();
+    let archive_path = archive_dir.path().join("redmine_archived.tar.gz");
+    let sha256 = create_tar_gz(&archive_path, &[("init.rb", "original")]);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: redmine_archived
+    archive:
+      url: "file://{}"
+      sha256: "{sha256}"
+"#,
+        archive_path.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install").current_dir(&temp_dir).assert().success();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("status")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("redmine_archived [plugin]: ok"));
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["reinstall", "redmine_archived"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Reinstalled redmine_archived"));
+
+    let plugin_dir = temp_dir.path().join("plugins/redmine_archived");
+    fs::write(plugin_dir.join("init.rb"), "hot patch").unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("status")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("redmine_archived [plugin]"));
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["reinstall", "redmine_archived"])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("use --force"));
+}
+
+/// Import tests
+#[test]
+fn test_import_unmanaged_extensions() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let plugin_repo = create_git_repo(repos_dir.path(), "legacy_plugin", &[("init.rb", "")]);
+    let theme_repo = create_git_repo(repos_dir.path(), "legacy_theme", &[("theme.css", "")]);
+    git(&["tag", "v1.0.0"], &theme_repo);
+
+    // Clones made by hand, plus a directory without git metadata
+    let plugins_dir = temp_dir.path().join("plugins");
+    let themes_dir = temp_dir.path().join("themes");
+    fs::create_dir_all(&plugins_dir).unwrap();
+    fs::create_dir_all(&themes_dir).unwrap();
+    git(
+        &[
+            "clone",
+            "-q",
+            &plugin_repo.to_string_lossy(),
+            "legacy_plugin",
+        ],
+        &plugins_dir,
+    );
+    git(
+        &["clone", "-q", &theme_repo.to_string_lossy(), "legacy_theme"],
+        &themes_dir,
+    );
+    git(
+        &["checkout", "-q", "v1.0.0"],
+        &themes_dir.join("legacy_theme"),
+    );
+    fs::create_dir_all(plugins_dir.join("copied_plugin")).unwrap();
+    fs::write(plugins_dir.join("legacy_plugin/local.txt"), "kept").unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("import")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Importing legacy_plugin"))
+        .stdout(predicate::str::contains("Imported 2 extension(s)"))
+        .stderr(predicate::str::contains("Skipping copied_plugin"));
+
+    let config_content = fs::read_to_string(temp_dir.path().join(".extensions.yml")).unwrap();
+    let config: serde_yaml::Value = serde_yaml::from_str(&config_content).unwrap();
+    assert_eq!(config["plugins"][0]["name"], "legacy_plugin");
+    assert_eq!(config["plugins"][0]["git"]["branch"], "main");
+    assert_eq!(config["themes"][0]["git"]["tag"], "v1.0.0");
+
+    let lock_content = fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap();
+    let lock_data: serde_json::Value = serde_json::from_str(&lock_content).unwrap();
+    assert_eq!(
+        lock_data["extensions"][0]["commit_hash"],
+        git(&["rev-parse", "HEAD"], &plugin_repo)
+    );
+
+    // The imported extensions are already installed as configured
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Extensions are up to date"));
+    assert!(plugins_dir.join("legacy_plugin/local.txt").exists());
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("import")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("No unmanaged extensions found"));
+
+    // Imports go to the default environment only
+    let lock_path = temp_dir.path().join(".extensions.lock");
+    let lock_content = fs::read_to_string(&lock_path)
+        .unwrap()
+        .replace("\"env\": \"default\"", "\"env\": \"staging\"");
+    fs::write(&lock_path, &lock_content).unwrap();
+    git(
+        &[
+            "clone",
+            "-q",
+            &plugin_repo.to_string_lossy(),
+            "later_plugin",
+        ],
+        &plugins_dir,
+    );
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("import")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "rex import only applies to the default environment, but staging is active",
+        ));
+    assert_eq!(fs::read_to_string(&lock_path).unwrap(), lock_content);
+    assert!(!fs::read_to_string(temp_dir.path().join(".extensions.yml"))
+        .unwrap()
+        .contains("later_plugin"));
+}
+
+/// Add and remove tests
+#[test]
+fn test_add_and_remove_keep_comments() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let repo = create_git_repo(repos_dir.path(), "added_plugin", &[("init.rb", "")]);
+    let pending_repo = create_git_repo(repos_dir.path(), "pending_plugin", &[("init.rb", "")]);
+
+    let config_content = format!(
+        r#"# Production extensions
+plugins:
+  # Not installed yet
+  - name: pending_plugin
+    git:
+      url: "{}"
+
+themes: []  # none yet
+"#,
+        pending_repo.display()
+    );
+    let config_path = temp_dir.path().join(".extensions.yml");
+    fs::write(&config_path, config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["add", "added_plugin", "--git"])
+        .arg(&repo)
+        .args(["--branch", "main", "--install"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Added added_plugin to .extensions.yml",
+        ));
+
+    let content = fs::read_to_string(&config_path).unwrap();
+    assert!(content.starts_with("# Production extensions\nplugins:\n  # Not installed yet\n"));
+    assert!(content.contains("  - name: added_plugin\n    git:\n"));
+    let config: serde_yaml::Value = serde_yaml::from_str(&content).unwrap();
+    assert_eq!(config["plugins"][1]["git"]["branch"], "main");
+
+    // Only the added extension is installed
+    assert!(temp_dir
+        .path()
+        .join("plugins/added_plugin/init.rb")
+        .exists());
+    assert!(!temp_dir.path().join("plugins/pending_plugin").exists());
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["add", "added_plugin", "--github", "owner/repo"])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already defined"));
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["add", "some_theme", "--theme", "--github", "owner/theme"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    let config: serde_yaml::Value =
+        serde_yaml::from_str(&fs::read_to_string(&config_path).unwrap()).unwrap();
+    assert_eq!(config["themes"][0]["github"]["repo"], "owner/theme");
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["remove", "added_plugin", "--install"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Removed added_plugin from .extensions.yml",
+        ));
+
+    assert!(!temp_dir.path().join("plugins/added_plugin").exists());
+    let content = fs::read_to_string(&config_path).unwrap();
+    assert!(content.contains("  # Not installed yet\n  - name: pending_plugin"));
+    assert!(!content.contains("added_plugin"));
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["remove", "missing_plugin"])
+        .current_dir(&temp_dir)
+        .assert()
+        .failure();
+
+    // --install only applies to the default environment
+    let lock_path = temp_dir.path().join(".extensions.lock");
+    let lock_content = fs::read_to_string(&lock_path)
+        .unwrap()
+        .replace("\"env\": \"default\"", "\"env\": \"staging\"");
+    fs::write(&lock_path, lock_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["add", "staged_plugin", "--git"])
+        .arg(&repo)
+        .arg("--install")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--install only applies to the default environment, but staging is active",
+        ));
+    assert!(!fs::read_to_string(&config_path)
+        .unwrap()
+        .contains("staged_plugin"));
+}
+
+/// Configuration validation tests
+#[test]
+fn test_check_reports_problems_with_locations() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let config_content = r#"plugins:
+  - name: typo_plugin
+    githb:
+      repo: owner/typo_plugin
+  - name: pinned_plugin
+    github:
+      repo: owner/pinned_plugin
+      tag: v1.0.0
+      branch: main
+themes:
+  - name: pinned_plugin
+    github:
+      repo: owner/theme
+  - name: "../escape"
+    github:
+      repo: owner/escape
+environments:
+  default:
+    plugins: []
+"#;
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("check")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            ".extensions.yml:3:5: unknown key `githb`, did you mean `github`?",
+        ))
+        .stderr(predicate::str::contains(
+            ".extensions.yml:5:5: `pinned_plugin`: only one of branch, tag, commit or version can be set",
+        ))
+        .stderr(predicate::str::contains(
+            ".extensions.yml:11:5: duplicate extension name `pinned_plugin`",
+        ))
+        .stderr(predicate::str::contains(
+            ".extensions.yml:14:5: invalid name `../escape`",
+        ))
+        .stderr(predicate::str::contains(
+            ".extensions.yml:18:3: the `default` environment is the top-level plugins and themes",
+        ))
+        .stderr(predicate::str::contains("5 problem(s) found"));
+
+    // Other commands refuse the configuration before touching anything
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(".extensions.yml:3:5"));
+    assert!(!temp_dir.path().join("plugins").exists());
+}
+
+#[test]
+fn test_check_valid_config() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // The same extension may appear in several environments
+    let config_content = r#"plugins:
+  - name: shared_plugin
+    github:
+      repo: owner/shared_plugin
+      version: "~> 1.2"
+environments:
+  staging:
+    plugins:
+      - name: shared_plugin
+        github:
+          repo: owner/shared_plugin
+          branch: develop
+"#;
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("check")
+        .current_dir(&temp_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(".extensions.yml is valid"));
+}
+
+/// Plugin dependency tests
+#[cfg(unix)]
+#[test]
+fn test_install_orders_plugins_by_dependencies() {
+    let temp_dir = TempDir::new().unwrap();
+    let tools_dir = TempDir::new().unwrap();
+    let (recorder, log) = create_command_recorder(tools_dir.path());
+    let dependent_repo = create_git_repo(
+        tools_dir.path(),
+        "dependent_plugin",
+        &[
+            (
+                "init.rb",
+                "Redmine::Plugin.register :dependent_plugin do\n  version '0.1.0'\n  requires_redmine_plugin :base_lib, version_or_higher: '1.0.0'\nend\n",
+            ),
+            ("db/migrate/001_create_items.rb", ""),
+        ],
+    );
+    let base_repo = create_git_repo(
+        tools_dir.path(),
+        "base_lib",
+        &[
+            (
+                "init.rb",
+                "Redmine::Plugin.register :base_lib do\n  version '1.2.0'\nend\n",
+            ),
+            ("db/migrate/001_create_base.rb", ""),
+        ],
+    );
+
+    let config_content = format!(
+        r#"plugins:
+  - name: dependent_plugin
+    git:
+      url: "{}"
+  - name: base_lib
+    git:
+      url: "{}"
+"#,
+        dependent_repo.display(),
+        base_repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_COMMAND_PREFIX", &recorder)
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+
+    // The required plugin is migrated first
+    let commands = fs::read_to_string(&log).unwrap();
+    let base = commands.find("NAME=base_lib").unwrap();
+    let dependent = commands.find("NAME=dependent_plugin").unwrap();
+    assert!(base < dependent);
+
+    // The lock file keeps the configured order
+    let lock_content = fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap();
+    let lock: serde_json::Value = serde_json::from_str(&lock_content).unwrap();
+    assert_eq!(lock["extensions"][0]["name"], "dependent_plugin");
+}
+
+#[test]
+fn test_install_fails_on_unmet_plugin_dependencies() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let dependent_repo = create_git_repo(
+        repos_dir.path(),
+        "dependent_plugin",
+        &[(
+            "init.rb",
+            "Redmine::Plugin.register :dependent_plugin do\n  requires_redmine_plugin :base_lib, :version_or_higher => '2.0'\n  requires_redmine_plugin :a_common_libs, '1.0'\nend\n",
+        )],
+    );
+    let base_repo = create_git_repo(
+        repos_dir.path(),
+        "base_lib",
+        &[(
+            "init.rb",
+            "Redmine::Plugin.register :base_lib do\n  version '1.2.0'\nend\n",
+        )],
+    );
+
+    let config_content = format!(
+        r#"plugins:
+  - name: dependent_plugin
+    git:
+      url: "{}"
+  - name: base_lib
+    git:
+      url: "{}"
+"#,
+        dependent_repo.display(),
+        base_repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "dependent_plugin requires base_lib 2.0 or higher, found 1.2.0",
+        ))
+        .stderr(predicate::str::contains(
+            "dependent_plugin requires plugin a_common_libs, which is neither in .extensions.yml nor installed",
+        ));
+
+    assert!(!temp_dir.path().join("plugins/dependent_plugin").exists());
+    assert!(!temp_dir.path().join(".extensions.lock").exists());
+}
+
+/// Redmine compatibility tests
+#[test]
+fn test_install_and_doctor_check_redmine_compatibility() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let version_rb = temp_dir.path().join("lib/redmine/version.rb");
+    fs::create_dir_all(version_rb.parent().unwrap()).unwrap();
+    fs::write(
+        &version_rb,
+        "module Redmine\n  module VERSION\n    MAJOR = 5\n    MINOR = 1\n    TINY  = 2\n  end\nend\n",
+    )
+    .unwrap();
+
+    let future_repo = create_git_repo(
+        repos_dir.path(),
+        "future_plugin",
+        &[(
+            "init.rb",
+            "Redmine::Plugin.register :future_plugin do\n  version '2.0.0'\n  requires_redmine version_or_higher: '6.0.0'\nend\n",
+        )],
+    );
+    let current_repo = create_git_repo(
+        repos_dir.path(),
+        "current_plugin",
+        &[(
+            "init.rb",
+            "Redmine::Plugin.register :current_plugin do\n  version '1.0.0'\n  requires_redmine :version => ['5.0', '5.1']\nend\n",
+        )],
+    );
+
+    let config_content = format!(
+        r#"plugins:
+  - name: future_plugin
+    git:
+      url: "{}"
+  - name: current_plugin
+    git:
+      url: "{}"
+"#,
+        future_repo.display(),
+        current_repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "future_plugin requires Redmine 6.0.0 or higher, found 5.1.2, use --ignore-compat",
+        ));
+    assert!(!temp_dir.path().join("plugins/current_plugin").exists());
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--ignore-compat"])
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    assert!(temp_dir.path().join("plugins/future_plugin").exists());
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("doctor")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Redmine 5.1.2"))
+        .stdout(predicate::str::contains("current_plugin (1.0.0): ok"))
+        .stdout(predicate::str::contains(
+            "future_plugin (2.0.0): future_plugin requires Redmine 6.0.0 or higher, found 5.1.2",
+        ))
+        .stderr(predicate::str::contains("1 plugin(s) have problems"));
+}
+
+/// Repository cache tests
+#[test]
+fn test_install_through_shared_mirror_cache() {
+    let first_root = TempDir::new().unwrap();
+    let second_root = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
+    let repo = create_git_repo(repos_dir.path(), "cached_plugin", &[("init.rb", "v1")]);
+    let url = format!("file://{}", repo.display());
+
+    let config_content = format!(
+        r#"plugins:
+  - name: cached_plugin
+    git:
+      url: "{url}"
+"#
+    );
+    for root in [&first_root, &second_root] {
+        fs::write(root.path().join(".extensions.yml"), &config_content).unwrap();
+    }
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&first_root)
+        .assert()
+        .success();
+
+    // The second Redmine root gets the commit added since the mirror was created
+    fs::write(repo.join("init.rb"), "v2").unwrap();
+    git(&["commit", "-q", "-am", "v2"], &repo);
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&second_root)
+        .assert()
+        .success();
+
+    let plugin_dir = second_root.path().join("plugins/cached_plugin");
+    assert_eq!(
+        fs::read_to_string(plugin_dir.join("init.rb")).unwrap(),
+        "v2"
+    );
+    assert_eq!(git(&["remote", "get-url", "origin"], &plugin_dir), url);
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["cache", "list"])
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(&url))
+        .stdout(predicate::str::contains("1 mirror(s)"));
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["cache", "prune"])
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed 0 mirror(s)"));
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["cache", "prune", "--days", "18446744073709551615"])
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed 0 mirror(s)"));
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["cache", "clean"])
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Removed 1 mirror(s)"));
+
+    // Installed extensions don't depend on the cache
+    assert_eq!(git(&["rev-parse", "HEAD"], &plugin_dir).len(), 40);
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["cache", "list"])
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("0 mirror(s)"));
+}
+
+/// Offline install tests
+#[test]
+fn test_offline_install_from_mirror_cache() {
+    let online_root = TempDir::new().unwrap();
+    let offline_root = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
+    let repo = create_git_repo(repos_dir.path(), "offline_plugin", &[("init.rb", "v1")]);
+    let locked_commit = git(&["rev-parse", "HEAD"], &repo);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: offline_plugin
+    git:
+      url: "file://{}"
+"#,
+        repo.display()
+    );
+    fs::write(online_root.path().join(".extensions.yml"), &config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&online_root)
+        .assert()
+        .success();
+
+    // Without a lock file there is nothing to install offline
+    fs::write(offline_root.path().join(".extensions.yml"), &config_content).unwrap();
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--offline"])
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&offline_root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--offline requires .extensions.lock",
+        ));
+
+    // The remote is gone, only the mirror is left
+    fs::copy(
+        online_root.path().join(".extensions.lock"),
+        offline_root.path().join(".extensions.lock"),
+    )
+    .unwrap();
+    let moved_repo = repos_dir.path().join("moved");
+    fs::rename(&repo, &moved_repo).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--offline"])
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&offline_root)
+        .assert()
+        .success();
+    let plugin_dir = offline_root.path().join("plugins/offline_plugin");
+    assert_eq!(git(&["rev-parse", "HEAD"], &plugin_dir), locked_commit);
+
+    // A locked commit the mirror never fetched can't be installed
+    fs::rename(&moved_repo, &repo).unwrap();
+    fs::write(repo.join("init.rb"), "v2").unwrap();
+    git(&["commit", "-q", "-am", "v2"], &repo);
+    let new_commit = git(&["rev-parse", "HEAD"], &repo);
+    let lock_path = offline_root.path().join(".extensions.lock");
+    let lock_content = fs::read_to_string(&lock_path).unwrap();
+    fs::write(
+        &lock_path,
+        lock_content.replace(&locked_commit, &new_commit),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--offline"])
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&offline_root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            " * offline_plugin: commit {} of file://{} is not in the mirror cache",
+            &new_commit[..8],
+            repo.display()
+        )))
+        .stderr(predicate::str::contains(
+            "1 extension(s) can't be installed offline",
+        ));
+    assert_eq!(git(&["rev-parse", "HEAD"], &plugin_dir), locked_commit);
+}
+
+/// Package tests
+#[test]
+fn test_install_from_package() {
+    let build_root = TempDir::new().unwrap();
+    let deploy_root = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
+    let repo = create_git_repo(repos_dir.path(), "packaged_plugin", &[("init.rb", "v1")]);
+    let locked_commit = git(&["rev-parse", "HEAD"], &repo);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: packaged_plugin
+    git:
+      url: "file://{}"
+"#,
+        repo.display()
+    );
+    fs::write(build_root.path().join(".extensions.yml"), &config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&build_root)
+        .assert()
+        .success();
+
+    // Newer commits upstream don't end up in the package
+    fs::write(repo.join("init.rb"), "v2").unwrap();
+    git(&["commit", "-q", "-am", "v2"], &repo);
+
+    let package = build_root.path().join("extensions.tar.gz");
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["package", "extensions.tar.gz"])
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&build_root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Packaged 1 extensions"));
+
+    // The server can't reach the repository
+    fs::remove_dir_all(&repo).unwrap();
+    fs::write(deploy_root.path().join(".extensions.yml"), &config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .arg("--from-package")
+        .arg(&package)
+        .env("REXER_CACHE_DIR", deploy_root.path().join("cache"))
+        .current_dir(&deploy_root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Installed 1 extensions from"));
+
+    let plugin_dir = deploy_root.path().join("plugins/packaged_plugin");
+    assert_eq!(
+        fs::read_to_string(plugin_dir.join("init.rb")).unwrap(),
+        "v1"
+    );
+    assert!(!plugin_dir.join(".git").exists());
+
+    let lock_content = fs::read_to_string(deploy_root.path().join(".extensions.lock")).unwrap();
+    assert!(lock_content.contains(&locked_commit));
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("status")
+        .current_dir(&deploy_root)
+        .assert()
+        .success();
+
+    // --frozen checks the package against the committed lock file
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--frozen", "--from-package"])
+        .arg(&package)
+        .current_dir(&deploy_root)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Extensions are up to date"));
+
+    let lock_path = deploy_root.path().join(".extensions.lock");
+    let lock_content = fs::read_to_string(&lock_path).unwrap();
+    fs::write(
+        &lock_path,
+        lock_content.replace(&locked_commit, &"0".repeat(40)),
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--offline", "--from-package"])
+        .arg(&package)
+        .current_dir(&deploy_root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("does not match .extensions.lock"));
+    fs::write(&lock_path, lock_content).unwrap();
+
+    // Without a .git directory there is nothing to update in place
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("update")
+        .env("REXER_CACHE_DIR", deploy_root.path().join("cache"))
+        .current_dir(&deploy_root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "is not a git repository, reinstall it with rex reinstall",
+        ));
+    assert_eq!(
+        fs::read_to_string(plugin_dir.join("init.rb")).unwrap(),
+        "v1"
+    );
+
+    // A package built for another configuration is refused
+    fs::write(
+        deploy_root.path().join(".extensions.yml"),
+        "plugins:\n  - name: other_plugin\n    git:\n      url: file:///nonexistent\n",
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .arg("--from-package")
+        .arg(&package)
+        .current_dir(&deploy_root)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("does not match .extensions.yml"));
+    assert!(plugin_dir.join("init.rb").exists());
+}
+
+/// Clone mode tests
+#[test]
+fn test_shallow_clones() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
+    let repo = create_git_repo(repos_dir.path(), "history_plugin", &[("init.rb", "v1")]);
+    let first_commit = git(&["rev-parse", "HEAD"], &repo);
+    for version in ["v2", "v3"] {
+        fs::write(repo.join("init.rb"), version).unwrap();
+        git(&["commit", "-q", "-am", version], &repo);
+    }
+    git(&["tag", "v2", "HEAD~1"], &repo);
+
+    let config_content = format!(
+        r#"clone: shallow
+plugins:
+  - name: tagged
+    git: {{ url: "file://{repo}", tag: "v2" }}
+  - name: pinned
+    git: {{ url: "file://{repo}", commit: "{first_commit}" }}
+  - name: tracking
+    git: {{ url: "file://{repo}" }}
+  - name: full
+    git: {{ url: "file://{repo}", clone: full }}
+"#,
+        repo = repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), &config_content).unwrap();
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("install")
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+
+    let plugin_dir = |name: &str| temp_dir.path().join("plugins").join(name);
+    let history = |name: &str| git(&["rev-list", "--count", "HEAD"], &plugin_dir(name));
+    let content = |name: &str| fs::read_to_string(plugin_dir(name).join("init.rb")).unwrap();
+    assert_eq!(
+        (history("tagged"), content("tagged")),
+        ("1".into(), "v2".into())
+    );
+    assert_eq!(
+        (history("pinned"), content("pinned")),
+        ("1".into(), "v1".into())
+    );
+    assert_eq!(
+        (history("tracking"), content("tracking")),
+        ("1".into(), "v3".into())
+    );
+    assert_eq!(
+        (history("full"), content("full")),
+        ("3".into(), "v3".into())
+    );
+
+    // Updates of a shallow clone stay shallow
+    fs::write(repo.join("init.rb"), "v4").unwrap();
+    git(&["commit", "-q", "-am", "v4"], &repo);
+
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["update", "tracking"])
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    assert_eq!(
+        (history("tracking"), content("tracking")),
+        ("1".into(), "v4".into())
+    );
+
+    // Switching the global mode leaves the installed extensions alone
+    fs::write(
+        temp_dir.path().join(".extensions.yml"),
+        config_content.replace("clone: shallow\n", ""),
+    )
+    .unwrap();
+    for args in [&["install", "--frozen"][..], &["install"]] {
+        let mut cmd = Command::cargo_bin("rex").unwrap();
+        cmd.args(args)
+            .env("REXER_CACHE_DIR", cache_dir.path())
+            .current_dir(&temp_dir)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("Extensions are up to date"));
+    }
+
+    // The new mode is recorded and used by the next update
+    let lock: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(temp_dir.path().join(".extensions.lock")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(lock["extensions"][2]["name"], "tracking");
+    assert!(lock["extensions"][2]["source"]["git"]["clone"].is_null());
+
+    fs::write(repo.join("init.rb"), "v5").unwrap();
+    git(&["commit", "-q", "-am", "v5"], &repo);
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["update", "tracking"])
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&temp_dir)
+        .assert()
+        .success();
+    assert_eq!(
+        (history("tracking"), content("tracking")),
+        ("5".into(), "v5".into())
+    );
+
+    // The mode is checked like the other options
+    fs::write(
+        temp_dir.path().join(".extensions.yml"),
+        format!(
+            "plugins:\n  - name: tagged\n    git: {{ url: \"file://{}\", clone: shalow }}\n",
+            repo.display()
+        ),
+    )
+    .unwrap();
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.arg("check")
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("shalow"));
+}
+
+/// Submodule tests
+#[test]
+fn test_install_and_update_submodules() {
+    let temp_dir = TempDir::new().unwrap();
+    let repos_dir = TempDir::new().unwrap();
+    let cache_dir = TempDir::new().unwrap();
+    let library = create_git_repo(repos_dir.path(), "library", &[("library.js", "v1")]);
+    let repo = create_git_repo(repos_dir.path(), "submodule_plugin", &[("init.rb", "")]);
+    let library_url = format!("file://{}", library.display());
+    git(
+        &[
+            "-c",
+            "protocol.file.allow=always",
+            "submodule",
+            "add",
+            "-q",
+            &library_url,
+            "vendor/library",
+        ],
+        &repo,
+    );
+    git(&["commit", "-q", "-m", "add library"], &repo);
+
+    let config_content = format!(
+        r#"plugins:
+  - name: with_submodules
+    git: {{ url: "file://{repo}", submodules: true }}
+  - name: without_submodules
+    git: {{ url: "file://{repo}" }}
+"#,
+        repo = repo.display()
+    );
+    fs::write(temp_dir.path().join(".extensions.yml"), config_content).unwrap();
+
+    // Git refuses file:// submodules unless allowed
+    let rex = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("rex").unwrap();
+        cmd.args(args)
+            .env("REXER_CACHE_DIR", cache_dir.path())
+            .env("GIT_CONFIG_COUNT", "1")
+            .env("GIT_CONFIG_KEY_0", "protocol.file.allow")
+            .env("GIT_CONFIG_VALUE_0", "always")
+            .current_dir(&temp_dir)
+            .assert()
+            .success();
+    };
+    rex(&["install"]);
+
+    let plugins_dir = temp_dir.path().join("plugins");
+    let library_file = plugins_dir.join("with_submodules/vendor/library/library.js");
+    assert_eq!(fs::read_to_string(&library_file).unwrap(), "v1");
+    assert!(!plugins_dir
+        .join("without_submodules/vendor/library/library.js")
+        .exists());
+
+    let lock_path = temp_dir.path().join(".extensions.lock");
+    let library_commit = git(&["rev-parse", "HEAD"], &library);
+    let lock: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&lock_path).unwrap()).unwrap();
+    assert_eq!(
+        lock["extensions"][0]["submodule_commits"]["vendor/library"],
+        library_commit.as_str()
+    );
+
+    // A new library commit recorded by the plugin is checked out on update
+    fs::write(library.join("library.js"), "v2").unwrap();
+    git(&["commit", "-q", "-am", "v2"], &library);
+    let submodule_dir = repo.join("vendor/library");
+    git(&["pull", "-q", "origin", "main"], &submodule_dir);
+    git(&["commit", "-q", "-am", "update library"], &repo);
+
+    rex(&["update"]);
+    assert_eq!(fs::read_to_string(&library_file).unwrap(), "v2");
+
+    let new_library_commit = git(&["rev-parse", "HEAD"], &library);
+    let lock: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&lock_path).unwrap()).unwrap();
+    assert_eq!(
+        lock["extensions"][0]["submodule_commits"]["vendor/library"],
+        new_library_commit.as_str()
+    );
+
+    // Submodules are not in the mirror cache
+    fs::remove_dir_all(plugins_dir.join("with_submodules")).unwrap();
+    let mut cmd = Command::cargo_bin("rex").unwrap();
+    cmd.args(["install", "--offline"])
+        .env("REXER_CACHE_DIR", cache_dir.path())
+        .current_dir(&temp_dir)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "with_submodules: extensions with submodules can't be installed offline",
+        ))
+        .stderr(predicate::str::contains("network access").not());
+}